@@ -0,0 +1,195 @@
+use crate::format::output::{self, PixelFormat};
+use crate::model::{ChannelConfig, Diagnostics, MaterialConfig, PipelineConfig};
+use crate::op::mip;
+use crate::naming::SourceDir;
+use crate::{pipeline, util};
+
+/// What to do when textures exceed their GPU memory budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetAction {
+    /// Warn about the textures, but build them as configured.
+    Warn,
+    /// Halve the `max_dimension` of materials until the budget is met.
+    Downscale,
+}
+
+impl BudgetAction {
+    /// Parses a budget action from its name in a pipeline file.
+    pub fn parse(name: &str) -> Option<BudgetAction> {
+        return match name {
+            "warn" => Some(BudgetAction::Warn),
+            "downscale" => Some(BudgetAction::Downscale),
+            _ => None,
+        };
+    }
+}
+
+/// Formats a number of bytes for display, such as `1.50 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = "bytes";
+    for next in units {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    if unit == "bytes" {
+        return format!("{0} bytes", bytes);
+    }
+    return format!("{0:.2} {1}", value, unit);
+}
+
+/// Returns the GPU memory taken by a texture of the given dimensions and pixel format, in bytes, with a full mip chain if `mips` is set.
+pub fn texture_bytes(width: u32, height: u32, format: PixelFormat, mips: bool) -> u64 {
+    let levels = if mips { mip::level_count(width, height) } else { 1 };
+    let block = match format {
+        PixelFormat::Block(_) => 4, // Blocks are 4x4 texels
+        _ => 1,
+    };
+    return (0..levels)
+        .map(|level| {
+            let w = (width >> level).max(1).div_ceil(block) as u64;
+            let h = (height >> level).max(1).div_ceil(block) as u64;
+            w * h * format.texel_block_size() as u64
+        })
+        .sum();
+}
+
+/// Returns the GPU pixel format of a channel of a material.
+pub fn channel_format(mat: &MaterialConfig, channel: &ChannelConfig) -> PixelFormat {
+    let mut ct = pipeline::output_color(&channel.name, channel.layout.as_ref(), mat.alpha);
+    if channel.name.eq("normal") {
+        ct = mat.format.normals.color_type();
+    }
+    return output::pixel_format(ct, &mat.format);
+}
+
+/// Estimates the GPU memory taken by a channel of a material at the given maximum dimension, in bytes.
+/// Output dimensions are predicted from the headers of the source textures, assuming square textures if there are none.
+pub fn channel_bytes(input_dir: &SourceDir, mat: &MaterialConfig, channel: &ChannelConfig, max_dimension: u32) -> u64 {
+    let layout = channel.layout.as_ref();
+    let sources = pipeline::channel_sources(input_dir, &mat.name, &channel.name, layout, &mat.unpack, &mat.naming);
+    let (width, height) = sources
+        .iter()
+        .filter_map(|path| image::image_dimensions(path).ok())
+        .map(|(w, h)| util::fit_dimensions(w, h, max_dimension, max_dimension))
+        .fold((max_dimension, max_dimension), |(width, height), (w, h)| (width.min(w), height.min(h)));
+    return texture_bytes(width, height, channel_format(mat, channel), mat.format.mips());
+}
+
+/// Estimates the GPU memory taken by every channel of a material at the given maximum dimension, in bytes.
+pub fn material_bytes(input_dir: &SourceDir, mat: &MaterialConfig, max_dimension: u32) -> u64 {
+    return mat
+        .channels
+        .iter()
+        .map(|channel| channel_bytes(input_dir, mat, channel, max_dimension))
+        .sum();
+}
+
+/// Checks the estimated GPU memory of each material, and of the whole pipeline, against their budgets.
+/// Depending on the budget action, this either warns, or halves the `max_dimension` of materials until their budgets are met.
+/// Problems and changes are recorded as warnings in `diag`.
+pub fn enforce(config: &mut PipelineConfig, input_dir: &SourceDir, diag: &mut Diagnostics) {
+    let original: Vec<u32> = config.materials.iter().map(|mat| mat.max_dimension).collect();
+
+    // Downscale materials that are over their own budget
+    let mut totals: Vec<u64> = Vec::new();
+    for mat in config.materials.iter_mut() {
+        let mut bytes = material_bytes(input_dir, mat, mat.max_dimension);
+        if let Some(budget) = mat.gpu_budget.filter(|_| mat.budget_action == BudgetAction::Downscale) {
+            while bytes > budget && mat.max_dimension > 1 {
+                mat.max_dimension /= 2;
+                bytes = material_bytes(input_dir, mat, mat.max_dimension);
+            }
+        }
+        totals.push(bytes);
+    }
+
+    // Then downscale the largest material until the whole pipeline fits
+    if let Some(budget) = config.gpu_budget.filter(|_| config.budget_action == BudgetAction::Downscale) {
+        while totals.iter().sum::<u64>() > budget {
+            let largest = (0..totals.len())
+                .filter(|i| config.materials[*i].max_dimension > 1)
+                .max_by_key(|i| totals[*i]);
+            let Some(i) = largest else {
+                break;
+            };
+            let mat = &mut config.materials[i];
+            mat.max_dimension /= 2;
+            totals[i] = material_bytes(input_dir, mat, mat.max_dimension);
+        }
+    }
+
+    for ((mat, bytes), original) in config.materials.iter().zip(totals.iter()).zip(original) {
+        if let Some(budget) = mat.gpu_budget.filter(|budget| bytes > budget) {
+            diag.warn(
+                &format!("materials.{0}.gpu_budget", mat.name),
+                &format!("estimated GPU memory of {0} exceeds budget of {1}", format_bytes(*bytes), format_bytes(budget)),
+            );
+        }
+        if mat.max_dimension != original {
+            diag.warn(
+                &format!("materials.{0}.max_dimension", mat.name),
+                &format!("reduced from {0} to {1} to fit GPU memory budget", original, mat.max_dimension),
+            );
+        }
+    }
+    let total: u64 = totals.iter().sum();
+    if let Some(budget) = config.gpu_budget.filter(|budget| total > *budget) {
+        diag.warn(
+            "gpu_budget",
+            &format!("estimated GPU memory of {0} exceeds budget of {1}", format_bytes(total), format_bytes(budget)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{enforce, format_bytes, texture_bytes};
+    use crate::format::bcn::BlockFormat;
+    use crate::format::output::PixelFormat;
+    use crate::model::{self, Diagnostics};
+    use crate::naming::SourceDir;
+
+    #[test]
+    fn enforce_budgets() {
+        assert_eq!(texture_bytes(4, 4, PixelFormat::Rgba8, false), 64);
+        assert_eq!(texture_bytes(4, 4, PixelFormat::Rgba8, true), 64 + 16 + 4);
+        assert_eq!(texture_bytes(8, 8, PixelFormat::Block(BlockFormat::Bc1), true), 32 + 8 + 8 + 8);
+        assert_eq!(format_bytes(1536 * 1024), "1.50 MiB");
+
+        let mut diag = Diagnostics::default();
+        let mut config = model::parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "gpu_budget": 20000,
+                "budget_action": "downscale",
+                "materials": {
+                    "mat_a": { "max_dimension": 64, "gpu_budget": "4KiB", "channels": ["basecolor"] },
+                    "mat_b": { "max_dimension": 64, "channels": ["basecolor"] },
+                    "mat_c": { "max_dimension": 32, "gpu_budget": 1024, "budget_action": "warn", "channels": ["basecolor"] }
+                }
+            }"#,
+            None,
+            &mut diag,
+        )
+        .expect("Valid config");
+        enforce(&mut config, &SourceDir::default(), &mut diag);
+
+        // 64x64 RGBA8 without mips is 16 KiB, so mat_a is halved to fit its own budget, then mat_b to fit the pipeline's
+        let dimensions: Vec<u32> = config.materials.iter().map(|mat| mat.max_dimension).collect();
+        assert_eq!(dimensions, vec![32, 32, 32]);
+        assert_eq!(
+            diag.warnings,
+            vec![
+                "materials.mat_a.max_dimension: reduced from 64 to 32 to fit GPU memory budget",
+                "materials.mat_b.max_dimension: reduced from 64 to 32 to fit GPU memory budget",
+                "materials.mat_c.gpu_budget: estimated GPU memory of 4.00 KiB exceeds budget of 1.00 KiB",
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the build manifest, written into the output directory.
+pub const MANIFEST_NAME: &str = ".ironpress-manifest.json";
+
+/// 64-bit FNV-1a hasher. Stable across runs and platforms, unlike the standard library's hashers.
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        return Fnv1a(0xcbf29ce484222325);
+    }
+}
+
+impl Fnv1a {
+    /// Feeds bytes into the hash.
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Feeds a string into the hash, terminated so that consecutive strings can't run together.
+    pub fn write_str(&mut self, string: &str) {
+        self.write(string.as_bytes());
+        self.write(&[0xff]);
+    }
+
+    /// Feeds the path and contents of a file into the hash, or a marker if the file does not exist.
+    pub fn write_file(&mut self, path: &Path) {
+        self.write_str(path.to_str().unwrap());
+        match fs::read(path) {
+            Ok(contents) => {
+                self.write(&(contents.len() as u64).to_le_bytes());
+                self.write(&contents);
+            }
+            Err(_) => self.write_str("missing"),
+        }
+    }
+
+    /// Returns the hash as a hexadecimal string.
+    pub fn finish(&self) -> String {
+        return format!("{0:016x}", self.0);
+    }
+}
+
+/// Record of the inputs each output was last built from, so unchanged outputs can be skipped.
+#[derive(Debug, Default)]
+pub struct Manifest {
+    /// Input hash for each output, by file name.
+    outputs: HashMap<String, String>,
+}
+
+impl Manifest {
+    /// Returns the path of the manifest for the given output directory.
+    pub fn path(output_dir: &Path) -> PathBuf {
+        return output_dir.join(MANIFEST_NAME);
+    }
+
+    /// Loads the manifest from the given output directory.
+    /// Returns an empty manifest if there is none, or if it was written by a different version of IronPress.
+    pub fn load(output_dir: &Path) -> Manifest {
+        let mut manifest = Manifest::default();
+        let Ok(contents) = fs::read_to_string(Manifest::path(output_dir)) else {
+            return manifest;
+        };
+        let Ok(value) = json::parse(&contents) else {
+            return manifest;
+        };
+        if value["version"].as_str() != Some(env!("CARGO_PKG_VERSION")) {
+            return manifest;
+        }
+        for (output, key) in value["outputs"].entries() {
+            if let Some(key) = key.as_str() {
+                manifest.outputs.insert(output.to_string(), key.to_string());
+            }
+        }
+        return manifest;
+    }
+
+    /// Writes the manifest into the given output directory.
+    pub fn save(&self, output_dir: &Path) -> std::io::Result<()> {
+        let mut outputs = json::JsonValue::new_object();
+        let mut names: Vec<&String> = self.outputs.keys().collect();
+        names.sort();
+        for name in names {
+            outputs[name.as_str()] = self.outputs[name].as_str().into();
+        }
+        let value = json::object! {
+            version: env!("CARGO_PKG_VERSION"),
+            outputs: outputs,
+        };
+        return fs::write(Manifest::path(output_dir), json::stringify_pretty(value, 4));
+    }
+
+    /// Returns true if the output was last built from inputs with the given hash.
+    pub fn is_current(&self, output: &str, key: &str) -> bool {
+        return self.outputs.get(output).is_some_and(|k| k == key);
+    }
+
+    /// Records the hash of the inputs an output was built from.
+    pub fn insert(&mut self, output: &str, key: &str) {
+        self.outputs.insert(output.to_string(), key.to_string());
+    }
+
+    /// Forgets an output, so it is rebuilt next time.
+    pub fn remove(&mut self, output: &str) {
+        self.outputs.remove(output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fnv1a, Manifest};
+    use crate::util::test_dir;
+
+    #[test]
+    fn manifest_round_trip() {
+        let mut hasher = Fnv1a::default();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), "af63dc4c8601ec8c");
+
+        let dir = test_dir("manifest_round_trip", &[]);
+        let mut manifest = Manifest::default();
+        manifest.insert("mat_a_basecolor.png", "0123");
+        manifest.insert("mat_a_normal.png", "4567");
+        manifest.remove("mat_a_normal.png");
+        manifest.save(&dir).expect("Save manifest");
+
+        let manifest = Manifest::load(&dir);
+        assert!(manifest.is_current("mat_a_basecolor.png", "0123"));
+        assert!(!manifest.is_current("mat_a_basecolor.png", "89ab"));
+        assert!(!manifest.is_current("mat_a_normal.png", "4567"));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::cache::Manifest;
+use crate::cli::verbosity;
+use crate::format::output;
+use crate::model::PipelineConfig;
+use crate::pipeline;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File extensions of every container that IronPress can output.
+const EXTENSIONS: [&str; 3] = ["png", "ktx2", "dds"];
+
+/// Deletes a file if it exists, printing its path. Returns FALSE if the file could not be deleted.
+fn remove(path: &Path) -> bool {
+    if !path.exists() {
+        return true;
+    }
+    if let Err(err) = fs::remove_file(path) {
+        eprintln!("\tFailed to remove {0}, {1}", path.to_str().unwrap(), err);
+        return false;
+    }
+    if !verbosity::is_quiet() {
+        println!("\tRemoved {0}", path.to_str().unwrap());
+    }
+    return true;
+}
+
+/// Deletes every texture an IronPress Pipeline JSON file outputs, in any container, including PNG mip levels, for the pipeline and each of its profiles.
+/// The build manifest is deleted too, but other files in the output directory are left alone.
+/// Returns TRUE if every output was deleted, FALSE otherwise.
+pub fn from_file(config_file: &Path, output: Option<&String>) -> bool {
+    let Some(config) = pipeline::load_config(config_file) else {
+        return false;
+    };
+    let mut targets = vec![(None, config.clone())];
+    for profile in config.profiles.iter() {
+        targets.extend(config.with_profile(&profile.name).map(|target| (Some(profile.name.clone()), target)));
+    }
+
+    let mut success = true;
+    for (profile, config) in targets {
+        let output = pipeline::profile_output(output, profile.as_deref());
+        let outdir: PathBuf = pipeline::output_dir(config_file, &config, output.as_ref());
+        success &= clean(&outdir, &config);
+    }
+    return success;
+}
+
+/// Deletes every texture a pipeline outputs into the given output directory, and its build manifest.
+/// Returns TRUE if every output was deleted, FALSE otherwise.
+fn clean(outdir: &Path, config: &PipelineConfig) -> bool {
+    let mut success = true;
+    for mat in config.materials.iter() {
+        for channel in mat.channels.iter() {
+            for extension in EXTENSIONS {
+                let path = mat.naming.output_path(outdir, &mat.name, &channel.name, extension);
+                success &= remove(&path);
+
+                // Mip levels written as their own files
+                let mut level = 1;
+                while output::mip_path(&path, level).exists() {
+                    success &= remove(&output::mip_path(&path, level));
+                    level += 1;
+                }
+            }
+        }
+    }
+    success &= remove(&Manifest::path(outdir));
+    return success;
+}
@@ -1,21 +1,136 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
 // https://docs.rs/clap/latest/clap/
 // https://docs.rs/clap/latest/clap/_derive/_tutorial/chapter_0/index.html
 
-/// Simple program to greet a person
+/// Launch arguments for IronPress.
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    after_help = "Exits with 0 on success, 1 if a command failed, and 2 for invalid arguments."
+)]
 pub struct CLIArguments {
-    /// Path of pipeline configuration file to operate with
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path of pipeline configuration file to build, same as `ironpress build <FILE>`
     // #[arg(short, long, value_name="FILE.json")]
-    pub file: String,
+    pub file: Option<String>,
 
-    /// Dumps a default IronPress configuration into the given file
-    #[arg(short, long)]
+    #[command(flatten)]
+    pub build: BuildOptions,
+
+    /// Dumps a default IronPress configuration into the given file, same as `ironpress init <FILE>`
+    #[arg(long, hide = true)]
     pub default: bool,
 
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print additional detail about each texture
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+}
+
+/// IronPress commands.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Builds all textures of a pipeline
+    Build {
+        /// Path of pipeline configuration file to build
+        file: String,
+
+        #[command(flatten)]
+        options: BuildOptions,
+    },
+    /// Builds all textures of a pipeline, then rebuilds them whenever the pipeline or its source maps change
+    Watch {
+        /// Path of pipeline configuration file to watch
+        file: String,
+
+        #[command(flatten)]
+        options: BuildOptions,
+
+        /// Milliseconds to wait after the last file change before rebuilding
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        debounce: u64,
+    },
+    /// Writes a default pipeline configuration file
+    Init {
+        /// Path to write the pipeline configuration file to
+        #[arg(default_value = "texture_pipeline.json")]
+        file: String,
+
+        /// Overwrite the file if it already exists
+        #[arg(short, long)]
+        force: bool,
+
+        /// Declare a material for every material found in the given input folder, relative to the pipeline file
+        #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "./input/")]
+        scan: Option<String>,
+    },
+    /// Checks a pipeline configuration and its source maps without processing any images
+    Validate {
+        /// Path of pipeline configuration file to validate
+        file: String,
+    },
+    /// Prints the dimensions and pixel format of texture files
+    Inspect {
+        /// Paths of texture files to inspect
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Deletes the textures that a pipeline outputs
+    Clean {
+        /// Path of pipeline configuration file to clean the outputs of
+        file: String,
+
+        /// Output directory to clean, instead of the one in the pipeline configuration
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<String>,
+    },
+}
+
+/// Options for building a pipeline.
+#[derive(Args, Debug, Default, Clone)]
+pub struct BuildOptions {
     /// Parses the configuration and builds textures, but does not output any files
-    #[arg(short, long)]
+    #[arg(short, long, visible_alias = "dry-run")]
     pub dryrun: bool,
+
+    /// Maximum number of textures to process at once [default: number of CPU cores]
+    #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub jobs: Option<u32>,
+
+    /// Approximate memory, in megabytes, that textures being processed at once may use.
+    /// Textures wait for others to finish while they would exceed it, and a texture larger than the budget is processed on its own
+    #[arg(long, value_name = "MB", value_parser = clap::value_parser!(u64).range(1..))]
+    pub memory_budget: Option<u64>,
+
+    /// Only build the given material (can be repeated)
+    #[arg(short, long = "material", value_name = "NAME")]
+    pub materials: Vec<String>,
+
+    /// Output directory, instead of the one in the pipeline configuration
+    #[arg(short, long, value_name = "DIR")]
+    pub output: Option<String>,
+
+    /// Rebuild every texture, even if its sources and settings are unchanged
+    #[arg(short, long)]
+    pub force: bool,
+
+    /// Writes a JSON report of every output texture to the given path, suffixed with the profile name when building a profile
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<String>,
+
+    /// Build the given profile of the pipeline (can be repeated)
+    #[arg(short, long = "profile", value_name = "NAME")]
+    pub profiles: Vec<String>,
+
+    /// Build every profile of the pipeline
+    #[arg(long, conflicts_with = "profiles")]
+    pub all_profiles: bool,
 }
@@ -1,3 +1,6 @@
+use crate::cli::verbosity;
+use crate::discover;
+use crate::naming::Naming;
 use std::{fs, path::Path};
 use json;
 
@@ -7,6 +10,7 @@ pub fn default() -> json::JsonValue {
         input: "./input/",
         output: "./output/",
         flip_normals: false,
+        format: "png",
         materials: {
             mat_example: {
                 max_dimension: 512, // Max dimension resolution of the file output.
@@ -25,17 +29,56 @@ pub fn default() -> json::JsonValue {
     }
 }
 
-/// Writes the default IronPress configuration to a file.
+/// Returns the default IronPress configuration, with a material for every material found in the given input folder.
+/// Returns `None` if no source textures were found.
+pub fn scanned(output: &Path, input: &str) -> Option<json::JsonValue> {
+    let input_dir = output.parent().unwrap_or(Path::new("")).join(input);
+    let materials = discover::scan(&input_dir, &Naming::default());
+    if materials.is_empty() {
+        eprintln!("no source textures found in {0}", input_dir.display());
+        return None;
+    }
+
+    let mut cfg = default();
+    cfg["input"] = input.into();
+    cfg["materials"] = json::JsonValue::new_object();
+    for (name, maps) in materials {
+        if !verbosity::is_quiet() {
+            println!("found material {0} with maps {1:?}", name, maps);
+        }
+        cfg["materials"][name.as_str()] = json::object! {
+            max_dimension: 512,
+            alpha: false,
+            channels: maps,
+        };
+    }
+    return Some(cfg);
+}
+
+/// Writes the default IronPress configuration to a file. Existing files are only overwritten if `force` is set.
+/// If `scan` is set, the configuration declares every material found in that input folder, relative to the file. See [`scanned`].
 /// Returns TRUE on successful write, FALSE otherwise.
-pub fn write_default(output: &Path) -> bool {
-    let cfg = default();
+pub fn write_default(output: &Path, scan: Option<&str>, force: bool) -> bool {
+    if output.exists() && !force {
+        eprintln!("{0} already exists, use --force to overwrite it", output.display());
+        return false;
+    }
+
+    let cfg = match scan {
+        Some(input) => match scanned(output, input) {
+            Some(cfg) => cfg,
+            None => return false,
+        },
+        None => default(),
+    };
 
     let res = fs::write(output, json::stringify_pretty(cfg, 4));
     match res.err() {
         Some(err) => {
-            println!("failed to output default config, {0}", err);
+            eprintln!("failed to output default config, {0}", err);
             return false;
         },
+        None if verbosity::is_quiet() => {}
         None => {
             match output.to_str() {
                 Some(str) => println!("output default config to {0}", str),
@@ -0,0 +1,28 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+const QUIET: u8 = 0;
+const NORMAL: u8 = 1;
+const VERBOSE: u8 = 2;
+
+/// Verbosity of the whole program, shared by all threads.
+static LEVEL: AtomicU8 = AtomicU8::new(NORMAL);
+
+/// Sets the verbosity from the `--quiet` and `--verbose` flags.
+pub fn set(quiet: bool, verbose: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => QUIET,
+        (_, true) => VERBOSE,
+        _ => NORMAL,
+    };
+    LEVEL.store(level, Ordering::Relaxed);
+}
+
+/// Returns true if only errors should be printed.
+pub fn is_quiet() -> bool {
+    return LEVEL.load(Ordering::Relaxed) == QUIET;
+}
+
+/// Returns true if additional detail should be printed.
+pub fn is_verbose() -> bool {
+    return LEVEL.load(Ordering::Relaxed) == VERBOSE;
+}
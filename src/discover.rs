@@ -0,0 +1,151 @@
+use crate::naming::Naming;
+use std::fs;
+use std::path::Path;
+
+/// Removes a suffix from a string, ignoring ASCII case.
+fn strip_suffix_ignore_case<'a>(text: &'a str, suffix: &str) -> Option<&'a str> {
+    let split = text.len().checked_sub(suffix.len())?;
+    if !text.is_char_boundary(split) || !text[split..].eq_ignore_ascii_case(suffix) {
+        return None;
+    }
+    return Some(&text[..split]);
+}
+
+/// Removes a prefix from a string, ignoring ASCII case.
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    if !text.is_char_boundary(prefix.len()) || !text[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    return Some(&text[prefix.len()..]);
+}
+
+/// Splits a file stem into the material and map names of a source file name template, if it matches.
+/// Only templates with exactly one `{material}` and one `{map}`, separated by some text, can be matched.
+/// `known` map names are matched first, longest first and ignoring case,
+/// so maps and aliases that contain the separator (such as `base_color`) stay whole.
+fn match_template(template: &str, stem: &str, known: &[&str]) -> Option<(String, String)> {
+    if template.matches("{material}").count() != 1 || template.matches("{map}").count() != 1 {
+        return None;
+    }
+    let material_first = template.find("{material}")? < template.find("{map}")?;
+    let (first, second) = match material_first {
+        true => ("{material}", "{map}"),
+        false => ("{map}", "{material}"),
+    };
+    let (prefix, rest) = template.split_once(first)?;
+    let (separator, suffix) = rest.split_once(second)?;
+    if separator.is_empty() {
+        return None;
+    }
+
+    let middle = stem.strip_prefix(prefix)?.strip_suffix(suffix)?;
+    let known_split = known.iter().find_map(|map| match material_first {
+        true => {
+            let material = strip_suffix_ignore_case(middle, map)?.strip_suffix(separator)?;
+            Some((material, &middle[middle.len() - map.len()..]))
+        }
+        false => {
+            let material = strip_prefix_ignore_case(middle, map)?.strip_prefix(separator)?;
+            Some((&middle[..map.len()], material))
+        }
+    });
+    // Otherwise, map names rarely contain the separator, while material names often do
+    let (a, b) = match (known_split, material_first) {
+        (Some(split), _) => split,
+        (None, true) => middle.rsplit_once(separator)?,
+        (None, false) => middle.split_once(separator)?,
+    };
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    return match material_first {
+        true => Some((a.to_string(), b.to_string())),
+        false => Some((b.to_string(), a.to_string())),
+    };
+}
+
+/// Scans a directory for source textures, grouping them by material and map with the given file name conventions.
+/// Map aliases are resolved to the name of the map they stand for, and extensions and map names are matched ignoring case.
+/// Returns each material with the maps found for it, both sorted by name.
+pub fn scan(input_dir: &Path, naming: &Naming) -> Vec<(String, Vec<String>)> {
+    let mut names: Vec<String> = match fs::read_dir(input_dir) {
+        Ok(entries) => entries.flatten().filter_map(|entry| entry.file_name().into_string().ok()).collect(),
+        Err(_) => return Vec::new(),
+    };
+    names.sort();
+
+    let mut known = naming.known_names();
+    known.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+    let mut materials: Vec<(String, Vec<String>)> = Vec::new();
+    for name in names {
+        let Some((stem, extension)) = name.rsplit_once('.') else {
+            continue;
+        };
+        if !naming.extensions.iter().any(|ext| ext.eq_ignore_ascii_case(extension)) {
+            continue;
+        }
+        let Some((material, map)) = naming.inputs.iter().find_map(|template| match_template(template, stem, &known)) else {
+            continue;
+        };
+
+        let map = naming.canonical_map(&map);
+        match materials.iter_mut().find(|(name, _)| *name == material) {
+            Some((_, maps)) if !maps.contains(&map) => maps.push(map),
+            Some(_) => {}
+            None => materials.push((material, vec![map])),
+        }
+    }
+
+    materials.sort();
+    for (_, maps) in materials.iter_mut() {
+        maps.sort();
+    }
+    return materials;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_template, scan};
+    use crate::naming::Naming;
+    use crate::util::test_dir;
+
+    #[test]
+    fn scan_materials() {
+        let known = ["ambient_occlusion", "base_color", "basecolor"];
+        let split = |material: &str, map: &str| Some((material.to_string(), map.to_string()));
+        assert_eq!(match_template("{material}_{map}", "mat_body_basecolor", &known), split("mat_body", "basecolor"));
+        assert_eq!(match_template("{material}_{map}", "mat_body_base_color", &known), split("mat_body", "base_color"));
+        assert_eq!(match_template("{material}_{map}", "mat_body_emissive", &known), split("mat_body", "emissive"));
+        assert_eq!(match_template("T_{map}-{material}", "T_nrm-rock_01", &known), split("rock_01", "nrm"));
+        assert_eq!(match_template("Body_{map}", "Body_BaseColor", &known), None);
+        assert_eq!(match_template("{material}_{map}", "readme", &known), None);
+
+        let files = [
+            "mat_rock_albedo.png",
+            "mat_rock_ambient_occlusion.png",
+            "mat_rock_base_color.png",
+            "mat_rock_nrm.tga",
+            "mat_rock_roughness.png",
+            "mat_rock_roughness.tif",
+            "mat_eye_basecolor.jpg",
+            "Body_BaseColor.PNG",
+            "xyz_4K_Albedo.jpg",
+            "mat_eye_emissive.png",
+            "notes.txt",
+            "preview.png",
+        ];
+        let dir = test_dir("discover", &files);
+
+        let materials = scan(&dir, &Naming::default());
+        assert_eq!(
+            materials,
+            vec![
+                ("Body".to_string(), vec!["basecolor".to_string()]),
+                ("mat_eye".to_string(), vec!["basecolor".to_string(), "emissive".to_string()]),
+                ("mat_rock".to_string(), vec!["ao".to_string(), "basecolor".to_string(), "normal".to_string(), "roughness".to_string()]),
+                ("xyz_4K".to_string(), vec!["basecolor".to_string()]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Reasons a texture could not be built.
+#[derive(Debug)]
+pub enum Error {
+    /// A source texture could not be opened or decoded.
+    Load { path: PathBuf, source: image::ImageError },
+    /// A map has no source texture of its own, nor a packed source that contains it.
+    MissingSource { path: PathBuf },
+    /// An image could not be encoded into its output format.
+    Encode { path: PathBuf, source: image::ImageError },
+    /// A file or directory could not be written.
+    Write { path: PathBuf, source: io::Error },
+    /// Processing panicked, with the given message.
+    Panic(String),
+}
+
+/// Result of a fallible stage of building a texture.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Error::Load { path, source } => write!(f, "failed to load {0}, {1}", path.display(), source),
+            Error::MissingSource { path } => write!(f, "source map not found at {0}", path.display()),
+            Error::Encode { path, source } => write!(f, "failed to encode {0}, {1}", path.display(), source),
+            Error::Write { path, source } => write!(f, "failed to write {0}, {1}", path.display(), source),
+            Error::Panic(message) => write!(f, "panicked, {0}", message),
+        };
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return match self {
+            Error::Load { source, .. } | Error::Encode { source, .. } => Some(source),
+            Error::Write { source, .. } => Some(source),
+            Error::MissingSource { .. } | Error::Panic(_) => None,
+        };
+    }
+}
@@ -0,0 +1,509 @@
+use image::RgbaImage;
+
+/// Block compression formats that IronPress can encode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// RGB color, 4 bits per pixel.
+    Bc1,
+    /// RGB color with interpolated alpha, 8 bits per pixel.
+    Bc3,
+    /// Single channel (red), 4 bits per pixel.
+    Bc4,
+    /// Two channels (red and green), 8 bits per pixel.
+    Bc5,
+    /// High quality RGBA color, 8 bits per pixel.
+    Bc7,
+}
+
+impl BlockFormat {
+    /// Returns the number of bytes per 4x4 block.
+    pub fn block_size(&self) -> usize {
+        return match self {
+            BlockFormat::Bc1 | BlockFormat::Bc4 => 8,
+            BlockFormat::Bc3 | BlockFormat::Bc5 | BlockFormat::Bc7 => 16,
+        };
+    }
+}
+
+/// A 4x4 block of RGBA texels, in row-major order.
+type Block = [[u8; 4]; 16];
+
+/// Compresses an image into the given block format, returning the blocks in row-major order.
+/// Images that are not a multiple of 4 in size are padded by repeating their edge texels.
+pub fn compress(img: &RgbaImage, format: BlockFormat) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+
+    let mut out: Vec<u8> = vec![0; (blocks_x * blocks_y) as usize * format.block_size()];
+    for (idx, chunk) in out.chunks_exact_mut(format.block_size()).enumerate() {
+        let block = extract_block(img, idx as u32 % blocks_x, idx as u32 / blocks_x);
+        match format {
+            BlockFormat::Bc1 => encode_color(&block, chunk),
+            BlockFormat::Bc3 => {
+                encode_alpha(&block.map(|t| t[3]), &mut chunk[0..8]);
+                encode_color(&block, &mut chunk[8..16]);
+            }
+            BlockFormat::Bc4 => encode_alpha(&block.map(|t| t[0]), chunk),
+            BlockFormat::Bc5 => {
+                encode_alpha(&block.map(|t| t[0]), &mut chunk[0..8]);
+                encode_alpha(&block.map(|t| t[1]), &mut chunk[8..16]);
+            }
+            BlockFormat::Bc7 => encode_bc7(&block, chunk),
+        }
+    }
+
+    return out;
+}
+
+/// Copies a 4x4 block of texels out of an image, clamping coordinates to the image edges.
+fn extract_block(img: &RgbaImage, block_x: u32, block_y: u32) -> Block {
+    let (width, height) = img.dimensions();
+    let mut block: Block = [[0; 4]; 16];
+    for (i, texel) in block.iter_mut().enumerate() {
+        let x = (block_x * 4 + i as u32 % 4).min(width - 1);
+        let y = (block_y * 4 + i as u32 / 4).min(height - 1);
+        *texel = img.get_pixel(x, y).0;
+    }
+    return block;
+}
+
+/// Finds the mean and principal axis of a set of points, for fitting block endpoints.
+/// The axis is zero if all points are equal.
+pub(crate) fn principal_axis<const N: usize>(points: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let count = points.len() as f32;
+    let mut mean = [0.0; N];
+    for p in points {
+        for c in 0..N {
+            mean[c] += p[c] / count;
+        }
+    }
+
+    // Covariance matrix
+    let mut cov = [[0.0; N]; N];
+    for p in points {
+        for i in 0..N {
+            for j in 0..N {
+                cov[i][j] += (p[i] - mean[i]) * (p[j] - mean[j]);
+            }
+        }
+    }
+
+    // Power iteration, seeded with the diagonal of the covariance matrix
+    let mut axis = [0.0; N];
+    for c in 0..N {
+        axis[c] = cov[c][c];
+    }
+    for _ in 0..8 {
+        let mut next = [0.0; N];
+        for i in 0..N {
+            for j in 0..N {
+                next[i] += cov[i][j] * axis[j];
+            }
+        }
+        let len = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len < 1e-6 {
+            return (mean, [0.0; N]);
+        }
+        axis = next.map(|v| v / len);
+    }
+
+    return (mean, axis);
+}
+
+/// Fits two endpoints to a set of points, along their principal axis.
+pub(crate) fn fit_endpoints<const N: usize>(points: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let (mean, axis) = principal_axis(points);
+    let mut min_t = 0.0f32;
+    let mut max_t = 0.0f32;
+    for p in points {
+        let t: f32 = (0..N).map(|c| (p[c] - mean[c]) * axis[c]).sum();
+        min_t = min_t.min(t);
+        max_t = max_t.max(t);
+    }
+
+    let mut start = [0.0; N];
+    let mut end = [0.0; N];
+    for c in 0..N {
+        start[c] = (mean[c] + axis[c] * max_t).clamp(0.0, 255.0);
+        end[c] = (mean[c] + axis[c] * min_t).clamp(0.0, 255.0);
+    }
+    return (start, end);
+}
+
+/// Returns the index of the palette entry closest to the given point.
+pub(crate) fn nearest<const N: usize>(palette: &[[f32; N]], point: &[f32; N]) -> usize {
+    let mut best = 0;
+    let mut best_dist = f32::MAX;
+    for (idx, entry) in palette.iter().enumerate() {
+        let dist: f32 = (0..N).map(|c| (entry[c] - point[c]).powi(2)).sum();
+        if dist < best_dist {
+            best = idx;
+            best_dist = dist;
+        }
+    }
+    return best;
+}
+
+/// Quantizes an 8-bit RGB color to RGB565.
+fn to_565(c: [f32; 3]) -> u16 {
+    let r = (c[0] * 31.0 / 255.0).round() as u16;
+    let g = (c[1] * 63.0 / 255.0).round() as u16;
+    let b = (c[2] * 31.0 / 255.0).round() as u16;
+    return (r << 11) | (g << 5) | b;
+}
+
+/// Expands an RGB565 color to 8-bit RGB.
+fn from_565(v: u16) -> [f32; 3] {
+    let r = (v >> 11) & 31;
+    let g = (v >> 5) & 63;
+    let b = v & 31;
+    return [
+        ((r << 3) | (r >> 2)) as f32,
+        ((g << 2) | (g >> 4)) as f32,
+        ((b << 3) | (b >> 2)) as f32,
+    ];
+}
+
+/// Encodes the RGB channels of a block as a BC1 color block (always in 4-color mode).
+fn encode_color(block: &Block, out: &mut [u8]) {
+    let points = block.map(|t| [t[0] as f32, t[1] as f32, t[2] as f32]);
+    let (start, end) = fit_endpoints(&points);
+
+    let mut c0 = to_565(start);
+    let mut c1 = to_565(end);
+    // 4-color mode requires the first endpoint to be larger
+    if c0 < c1 {
+        (c0, c1) = (c1, c0);
+    }
+
+    let mut indices: u32 = 0;
+    if c0 != c1 {
+        let p0 = from_565(c0);
+        let p1 = from_565(c1);
+        let palette = [
+            p0,
+            p1,
+            [0, 1, 2].map(|c| (2.0 * p0[c] + p1[c]) / 3.0),
+            [0, 1, 2].map(|c| (p0[c] + 2.0 * p1[c]) / 3.0),
+        ];
+        for (i, p) in points.iter().enumerate() {
+            indices |= (nearest(&palette, p) as u32) << (2 * i);
+        }
+    }
+
+    out[0..2].copy_from_slice(&c0.to_le_bytes());
+    out[2..4].copy_from_slice(&c1.to_le_bytes());
+    out[4..8].copy_from_slice(&indices.to_le_bytes());
+}
+
+/// Encodes a block of single-channel values as a BC4 block (always in 8-value mode).
+/// Also used for the alpha block of BC3, and each channel of BC5.
+fn encode_alpha(values: &[u8; 16], out: &mut [u8]) {
+    let a0 = *values.iter().max().unwrap();
+    let a1 = *values.iter().min().unwrap();
+
+    let mut indices: u64 = 0;
+    if a0 != a1 {
+        let mut palette = [[a0 as f32], [a1 as f32], [0.0], [0.0], [0.0], [0.0], [0.0], [0.0]];
+        for i in 1..7 {
+            palette[i + 1] = [((7 - i) as f32 * a0 as f32 + i as f32 * a1 as f32) / 7.0];
+        }
+        for (i, v) in values.iter().enumerate() {
+            indices |= (nearest(&palette, &[*v as f32]) as u64) << (3 * i);
+        }
+    }
+
+    out[0] = a0;
+    out[1] = a1;
+    out[2..8].copy_from_slice(&indices.to_le_bytes()[0..6]);
+}
+
+/// Interpolation weights for BC7 4-bit indices, out of 64.
+const BC7_WEIGHTS: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+/// Interpolation weights for BC7 2-bit indices, out of 64.
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+
+/// Quantizes an 8-bit RGBA endpoint to 7 bits per channel plus a shared p-bit, choosing whichever p-bit is more accurate.
+fn quantize_bc7_endpoint(endpoint: [f32; 4]) -> ([u8; 4], u8) {
+    let mut best = ([0; 4], 0);
+    let mut best_err = f32::MAX;
+    for p in 0..2u8 {
+        let q = endpoint.map(|v| ((v - p as f32) / 2.0).round().clamp(0.0, 127.0) as u8);
+        let err: f32 = (0..4)
+            .map(|c| ((q[c] * 2 + p) as f32 - endpoint[c]).powi(2))
+            .sum();
+        if err < best_err {
+            best = (q, p);
+            best_err = err;
+        }
+    }
+    return best;
+}
+
+/// Writes values into a little-endian bit stream.
+struct BitWriter<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl BitWriter<'_> {
+    /// Writes the lowest `bits` bits of `value`, least significant first.
+    fn write(&mut self, value: u32, bits: usize) {
+        for i in 0..bits {
+            if (value >> i) & 1 == 1 {
+                self.out[self.pos / 8] |= 1 << (self.pos % 8);
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+/// Interpolates between two BC7 endpoint values with a weight out of 64.
+fn bc7_interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    return ((64 - weight) * e0 + weight * e1 + 32) >> 6;
+}
+
+/// Encodes a block as BC7 mode 6: a single subset with 7-bit RGBA endpoints, p-bits, and 4-bit indices.
+/// Returns the squared error of the encoded block.
+fn encode_bc7_mode6(block: &Block, out: &mut [u8]) -> f32 {
+    let points = block.map(|t| t.map(|v| v as f32));
+    let (start, end) = fit_endpoints(&points);
+
+    let mut endpoints = [quantize_bc7_endpoint(start), quantize_bc7_endpoint(end)];
+    let decode = |(q, p): ([u8; 4], u8)| q.map(|v| (v * 2 + p) as u32);
+    let (e0, e1) = (decode(endpoints[0]), decode(endpoints[1]));
+    let palette: Vec<[f32; 4]> = BC7_WEIGHTS
+        .iter()
+        .map(|w| [0, 1, 2, 3].map(|c| bc7_interpolate(e0[c], e1[c], *w) as f32))
+        .collect();
+
+    let mut indices = [0u32; 16];
+    let mut error = 0.0;
+    for (i, p) in points.iter().enumerate() {
+        let index = nearest(&palette, p);
+        indices[i] = index as u32;
+        error += (0..4).map(|c| (palette[index][c] - p[c]).powi(2)).sum::<f32>();
+    }
+
+    // The first index is stored with an implicit high bit of zero, so swap endpoints if needed
+    if indices[0] >= 8 {
+        endpoints.swap(0, 1);
+        indices = indices.map(|i| 15 - i);
+    }
+
+    out.fill(0);
+    let mut writer = BitWriter { out, pos: 0 };
+    writer.write(1 << 6, 7); // Mode 6
+    for c in 0..4 {
+        writer.write(endpoints[0].0[c] as u32, 7);
+        writer.write(endpoints[1].0[c] as u32, 7);
+    }
+    writer.write(endpoints[0].1 as u32, 1);
+    writer.write(endpoints[1].1 as u32, 1);
+    writer.write(indices[0], 3);
+    for index in indices.iter().skip(1) {
+        writer.write(*index, 4);
+    }
+
+    return error;
+}
+
+/// Encodes a block as BC7 mode 5: a single subset with separate 7-bit RGB and 8-bit alpha endpoints, each with their own 2-bit indices.
+/// Returns the squared error of the encoded block.
+fn encode_bc7_mode5(block: &Block, out: &mut [u8]) -> f32 {
+    let colors = block.map(|t| [t[0] as f32, t[1] as f32, t[2] as f32]);
+    let (start, end) = fit_endpoints(&colors);
+    let mut color_endpoints = [start, end].map(|e| e.map(|v| (v * 127.0 / 255.0).round() as u32));
+    let expand = |q: [u32; 3]| q.map(|v| (v << 1) | (v >> 6));
+    let (e0, e1) = (expand(color_endpoints[0]), expand(color_endpoints[1]));
+    let color_palette: Vec<[f32; 3]> = BC7_WEIGHTS_2
+        .iter()
+        .map(|w| [0, 1, 2].map(|c| bc7_interpolate(e0[c], e1[c], *w) as f32))
+        .collect();
+
+    let alphas = block.map(|t| [t[3] as f32]);
+    let mut alpha_endpoints = [
+        block.iter().map(|t| t[3]).min().unwrap() as u32,
+        block.iter().map(|t| t[3]).max().unwrap() as u32,
+    ];
+    let alpha_palette: Vec<[f32; 1]> = BC7_WEIGHTS_2
+        .iter()
+        .map(|w| [bc7_interpolate(alpha_endpoints[0], alpha_endpoints[1], *w) as f32])
+        .collect();
+
+    let mut color_indices = [0u32; 16];
+    let mut alpha_indices = [0u32; 16];
+    let mut error = 0.0;
+    for i in 0..16 {
+        let color = nearest(&color_palette, &colors[i]);
+        let alpha = nearest(&alpha_palette, &alphas[i]);
+        color_indices[i] = color as u32;
+        alpha_indices[i] = alpha as u32;
+        error += (0..3).map(|c| (color_palette[color][c] - colors[i][c]).powi(2)).sum::<f32>();
+        error += (alpha_palette[alpha][0] - alphas[i][0]).powi(2);
+    }
+
+    // The first indices are stored with an implicit high bit of zero, so swap endpoints if needed
+    if color_indices[0] >= 2 {
+        color_endpoints.swap(0, 1);
+        color_indices = color_indices.map(|i| 3 - i);
+    }
+    if alpha_indices[0] >= 2 {
+        alpha_endpoints.swap(0, 1);
+        alpha_indices = alpha_indices.map(|i| 3 - i);
+    }
+
+    out.fill(0);
+    let mut writer = BitWriter { out, pos: 0 };
+    writer.write(1 << 5, 6); // Mode 5
+    writer.write(0, 2); // No channel rotation
+    let [color0, color1] = color_endpoints;
+    for (c0, c1) in color0.iter().zip(color1.iter()) {
+        writer.write(*c0, 7);
+        writer.write(*c1, 7);
+    }
+    writer.write(alpha_endpoints[0], 8);
+    writer.write(alpha_endpoints[1], 8);
+    for indices in [color_indices, alpha_indices] {
+        writer.write(indices[0], 1);
+        for index in indices.iter().skip(1) {
+            writer.write(*index, 2);
+        }
+    }
+
+    return error;
+}
+
+/// Encodes a block as BC7, using whichever of modes 5 and 6 is more accurate.
+fn encode_bc7(block: &Block, out: &mut [u8]) {
+    let mut mode6 = [0u8; 16];
+    let mut mode5 = [0u8; 16];
+    let error6 = encode_bc7_mode6(block, &mut mode6);
+    let error5 = encode_bc7_mode5(block, &mut mode5);
+    out.copy_from_slice(if error5 < error6 { &mode5 } else { &mode6 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, from_565, BlockFormat, BC7_WEIGHTS, BC7_WEIGHTS_2};
+    use image::{Rgba, RgbaImage};
+
+    /// Decodes a BC1 color block into RGB texels.
+    fn decode_color(block: &[u8]) -> Vec<[f32; 3]> {
+        let c0 = u16::from_le_bytes([block[0], block[1]]);
+        let c1 = u16::from_le_bytes([block[2], block[3]]);
+        let (p0, p1) = (from_565(c0), from_565(c1));
+        let palette = [
+            p0,
+            p1,
+            [0, 1, 2].map(|c| (2.0 * p0[c] + p1[c]) / 3.0),
+            [0, 1, 2].map(|c| (p0[c] + 2.0 * p1[c]) / 3.0),
+        ];
+        let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+        return (0..16).map(|i| palette[((indices >> (2 * i)) & 3) as usize]).collect();
+    }
+
+    /// Decodes a BC4 block into single-channel texels.
+    fn decode_alpha(block: &[u8]) -> Vec<f32> {
+        let (a0, a1) = (block[0] as f32, block[1] as f32);
+        let mut bytes = [0u8; 8];
+        bytes[0..6].copy_from_slice(&block[2..8]);
+        let indices = u64::from_le_bytes(bytes);
+        return (0..16)
+            .map(|i| match (indices >> (3 * i)) & 7 {
+                0 => a0,
+                1 => a1,
+                n => ((8 - n) as f32 * a0 + (n - 1) as f32 * a1) / 7.0,
+            })
+            .collect();
+    }
+
+    /// Decodes a BC7 mode 5 or mode 6 block into RGBA texels.
+    fn decode_bc7(block: &[u8]) -> Vec<[u32; 4]> {
+        let bits = u128::from_le_bytes(block.try_into().unwrap());
+        let read = |offset: u32, len: u32| ((bits >> offset) & ((1u128 << len) - 1)) as u32;
+        let lerp = |e0: u32, e1: u32, w: u32| ((64 - w) * e0 + w * e1 + 32) >> 6;
+
+        if read(0, 7) == 1 << 6 {
+            let (p0, p1) = (read(63, 1), read(64, 1));
+            let e0: Vec<u32> = (0..4).map(|c| read(7 + c * 14, 7) * 2 + p0).collect();
+            let e1: Vec<u32> = (0..4).map(|c| read(14 + c * 14, 7) * 2 + p1).collect();
+            return (0..16)
+                .map(|i| {
+                    let index = if i == 0 { read(65, 3) } else { read(64 + i * 4, 4) };
+                    let w = BC7_WEIGHTS[index as usize];
+                    [0, 1, 2, 3].map(|c| lerp(e0[c], e1[c], w))
+                })
+                .collect();
+        }
+
+        assert_eq!(read(0, 6), 1 << 5, "Expected mode 5 or 6 block");
+        let expand = |v: u32| (v << 1) | (v >> 6);
+        let e0: Vec<u32> = (0..3).map(|c| expand(read(8 + c * 14, 7))).collect();
+        let e1: Vec<u32> = (0..3).map(|c| expand(read(15 + c * 14, 7))).collect();
+        let (a0, a1) = (read(50, 8), read(58, 8));
+        let index = |start: u32, i: u32| if i == 0 { read(start, 1) } else { read(start - 1 + i * 2, 2) };
+        return (0..16)
+            .map(|i| {
+                let wc = BC7_WEIGHTS_2[index(66, i) as usize];
+                let wa = BC7_WEIGHTS_2[index(97, i) as usize];
+                [lerp(e0[0], e1[0], wc), lerp(e0[1], e1[1], wc), lerp(e0[2], e1[2], wc), lerp(a0, a1, wa)]
+            })
+            .collect();
+    }
+
+    #[test]
+    fn compress_gradients() {
+        // A gradient that doesn't fit evenly into blocks
+        let img = RgbaImage::from_fn(6, 5, |x, y| {
+            let t = (x + 4 * y) * 4;
+            Rgba([t as u8, (t / 2 + 20) as u8, (200 - t) as u8, (x * y * 10) as u8])
+        });
+
+        let bc1 = compress(&img, BlockFormat::Bc1);
+        assert_eq!(bc1.len(), 4 * 8);
+        for (texel, expected) in decode_color(&bc1[0..8]).iter().zip(0..16) {
+            let px = img.get_pixel(expected % 4, expected / 4).0;
+            for c in 0..3 {
+                assert!((texel[c] - px[c] as f32).abs() <= 16.0, "BC1 error too large");
+            }
+        }
+
+        let bc3 = compress(&img, BlockFormat::Bc3);
+        assert_eq!(bc3.len(), 4 * 16);
+        for (value, expected) in decode_alpha(&bc3[0..8]).iter().zip(0..16) {
+            let px = img.get_pixel(expected % 4, expected / 4).0;
+            assert!((value - px[3] as f32).abs() <= 7.0, "BC3 alpha error too large");
+        }
+
+        let bc5 = compress(&img, BlockFormat::Bc5);
+        assert_eq!(bc5.len(), 4 * 16);
+        for (value, expected) in decode_alpha(&bc5[8..16]).iter().zip(0..16) {
+            let px = img.get_pixel(expected % 4, expected / 4).0;
+            assert!((value - px[1] as f32).abs() <= 3.0, "BC5 green error too large");
+        }
+
+        let bc7 = compress(&img, BlockFormat::Bc7);
+        assert_eq!(bc7.len(), 4 * 16);
+        for (texel, expected) in decode_bc7(&bc7[0..16]).iter().zip(0..16) {
+            let px = img.get_pixel(expected % 4, expected / 4).0;
+            for c in 0..4 {
+                assert!(texel[c].abs_diff(px[c] as u32) <= 10, "BC7 error too large");
+            }
+        }
+
+        // Opaque color should use the full index precision of BC7
+        let opaque = RgbaImage::from_fn(4, 4, |x, y| {
+            let px = img.get_pixel(x, y).0;
+            Rgba([px[0], px[1], px[2], 255])
+        });
+        let bc7 = compress(&opaque, BlockFormat::Bc7);
+        for (texel, expected) in decode_bc7(&bc7).iter().zip(0..16) {
+            let px = opaque.get_pixel(expected % 4, expected / 4).0;
+            for c in 0..4 {
+                assert!(texel[c].abs_diff(px[c] as u32) <= 4, "Opaque BC7 error too large");
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::format::bcn::BlockFormat;
+use crate::format::output::PixelFormat;
+
+// https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dds-header
+// https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dds-header-dxt10
+
+/// Flags marking which header fields are valid.
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+/// Surface capabilities.
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+/// Pixel format flag, marking that the format is given by a FourCC code.
+const DDPF_FOURCC: u32 = 0x4;
+/// Resource dimension of a 2D texture, for the DX10 header.
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+/// Returns the DXGI format identifier for the given pixel format.
+pub fn dxgi_format(format: PixelFormat, srgb: bool) -> u32 {
+    return match (format, srgb) {
+        (PixelFormat::R8, _) => 61,           // DXGI_FORMAT_R8_UNORM
+        (PixelFormat::Rg8, _) => 49,          // DXGI_FORMAT_R8G8_UNORM
+        (PixelFormat::Rgba8, false) => 28,    // DXGI_FORMAT_R8G8B8A8_UNORM
+        (PixelFormat::Rgba8, true) => 29,     // DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+        (PixelFormat::R16, _) => 56,          // DXGI_FORMAT_R16_UNORM
+        (PixelFormat::Rg16, _) => 35,         // DXGI_FORMAT_R16G16_UNORM
+        (PixelFormat::Rgba16, _) => 11,       // DXGI_FORMAT_R16G16B16A16_UNORM
+        (PixelFormat::Block(block), _) => match (block, srgb) {
+            (BlockFormat::Bc1, false) => 71,  // DXGI_FORMAT_BC1_UNORM
+            (BlockFormat::Bc1, true) => 72,   // DXGI_FORMAT_BC1_UNORM_SRGB
+            (BlockFormat::Bc3, false) => 77,  // DXGI_FORMAT_BC3_UNORM
+            (BlockFormat::Bc3, true) => 78,   // DXGI_FORMAT_BC3_UNORM_SRGB
+            (BlockFormat::Bc4, _) => 80,      // DXGI_FORMAT_BC4_UNORM
+            (BlockFormat::Bc5, _) => 83,      // DXGI_FORMAT_BC5_UNORM
+            (BlockFormat::Bc7, false) => 98,  // DXGI_FORMAT_BC7_UNORM
+            (BlockFormat::Bc7, true) => 99,   // DXGI_FORMAT_BC7_UNORM_SRGB
+        },
+    };
+}
+
+/// Encodes a DDS file from the encoded data of each mip level, starting at the base level.
+/// Always uses the DX10 header extension, so every format (including sRGB and BC7) is described the same way.
+pub fn encode(levels: &[Vec<u8>], width: u32, height: u32, format: PixelFormat, srgb: bool) -> Vec<u8> {
+    let block_compressed = matches!(format, PixelFormat::Block(_));
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+    // Block compressed formats give the size of the base level, others give the size of a row
+    let pitch_or_linear_size = match block_compressed {
+        true => {
+            flags |= DDSD_LINEARSIZE;
+            levels[0].len() as u32
+        }
+        false => {
+            flags |= DDSD_PITCH;
+            width * format.texel_block_size() as u32
+        }
+    };
+    let mut caps = DDSCAPS_TEXTURE;
+    if levels.len() > 1 {
+        flags |= DDSD_MIPMAPCOUNT;
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+
+    let mut out: Vec<u8> = Vec::new();
+    out.extend_from_slice(b"DDS ");
+    for value in [124, flags, height, width, pitch_or_linear_size, 0, levels.len() as u32] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&[0u8; 44]); // dwReserved1
+
+    // Pixel format, deferring to the DX10 header
+    for value in [32, DDPF_FOURCC] {
+        out.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    out.extend_from_slice(b"DX10");
+    out.extend_from_slice(&[0u8; 20]); // Bit counts and masks
+
+    for value in [caps, 0, 0, 0, 0] {
+        out.extend_from_slice(&u32::to_le_bytes(value));
+    }
+
+    // DX10 header extension
+    for value in [dxgi_format(format, srgb), D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, 1, 0] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    for level in levels {
+        out.extend_from_slice(level);
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encode;
+    use crate::format::bcn::BlockFormat;
+    use crate::format::output::PixelFormat;
+    use ddsfile::{Dds, DxgiFormat};
+
+    #[test]
+    fn encode_readable() {
+        // BC7 8x8 with a full mip chain, where the smaller mips still take up a full block
+        let levels = vec![vec![1u8; 64], vec![2u8; 16], vec![3u8; 16], vec![4u8; 16]];
+        let bytes = encode(&levels, 8, 8, PixelFormat::Block(BlockFormat::Bc7), true);
+        let dds = Dds::read(bytes.as_slice()).expect("Parse DDS file");
+        assert_eq!(dds.get_dxgi_format(), Some(DxgiFormat::BC7_UNorm_sRGB));
+        assert_eq!((dds.get_width(), dds.get_height(), dds.get_num_mipmap_levels()), (8, 8, 4));
+        assert_eq!(dds.get_data(0).expect("Read texture data"), levels.concat().as_slice());
+
+        // Uncompressed single channel without mips
+        let levels = vec![vec![5u8; 6]];
+        let bytes = encode(&levels, 3, 2, PixelFormat::R8, false);
+        let dds = Dds::read(bytes.as_slice()).expect("Parse DDS file");
+        assert_eq!(dds.get_dxgi_format(), Some(DxgiFormat::R8_UNorm));
+        assert_eq!(dds.get_pitch(), Some(3));
+        assert_eq!(dds.get_data(0).expect("Read texture data"), levels[0].as_slice());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::format::bcn::BlockFormat;
+use crate::format::output::{PixelFormat, Supercompression};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+// https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html
+// https://registry.khronos.org/DataFormat/specs/1.3/dataformat.1.3.html
+
+/// File identifier at the start of every KTX2 file.
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+/// Length of the header and section index, before the level index.
+const HEADER_LENGTH: usize = 80;
+/// Length of a single entry in the level index.
+const LEVEL_INDEX_LENGTH: usize = 24;
+
+/// Data format descriptor color models.
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_MODEL_BC1A: u8 = 128;
+const KHR_DF_MODEL_BC3: u8 = 130;
+const KHR_DF_MODEL_BC4: u8 = 131;
+const KHR_DF_MODEL_BC5: u8 = 132;
+const KHR_DF_MODEL_BC7: u8 = 134;
+/// Data format descriptor channel IDs.
+const KHR_DF_CHANNEL_RED: u8 = 0;
+const KHR_DF_CHANNEL_GREEN: u8 = 1;
+const KHR_DF_CHANNEL_BLUE: u8 = 2;
+const KHR_DF_CHANNEL_ALPHA: u8 = 15;
+/// Sample qualifier marking a channel as linear, even if the texture uses the sRGB transfer function.
+const KHR_DF_SAMPLE_DATATYPE_LINEAR: u8 = 0x10;
+
+/// Returns the Vulkan format identifier for the given pixel format.
+pub fn vk_format(format: PixelFormat, srgb: bool) -> u32 {
+    return match (format, srgb) {
+        (PixelFormat::R8, false) => 9,        // VK_FORMAT_R8_UNORM
+        (PixelFormat::R8, true) => 15,        // VK_FORMAT_R8_SRGB
+        (PixelFormat::Rg8, false) => 16,      // VK_FORMAT_R8G8_UNORM
+        (PixelFormat::Rg8, true) => 22,       // VK_FORMAT_R8G8_SRGB
+        (PixelFormat::Rgba8, false) => 37,    // VK_FORMAT_R8G8B8A8_UNORM
+        (PixelFormat::Rgba8, true) => 43,     // VK_FORMAT_R8G8B8A8_SRGB
+        (PixelFormat::R16, _) => 70,          // VK_FORMAT_R16_UNORM
+        (PixelFormat::Rg16, _) => 77,         // VK_FORMAT_R16G16_UNORM
+        (PixelFormat::Rgba16, _) => 91,       // VK_FORMAT_R16G16B16A16_UNORM
+        (PixelFormat::Block(block), _) => match (block, srgb) {
+            (BlockFormat::Bc1, false) => 131, // VK_FORMAT_BC1_RGB_UNORM_BLOCK
+            (BlockFormat::Bc1, true) => 132,  // VK_FORMAT_BC1_RGB_SRGB_BLOCK
+            (BlockFormat::Bc3, false) => 137, // VK_FORMAT_BC3_UNORM_BLOCK
+            (BlockFormat::Bc3, true) => 138,  // VK_FORMAT_BC3_SRGB_BLOCK
+            (BlockFormat::Bc4, _) => 139,     // VK_FORMAT_BC4_UNORM_BLOCK
+            (BlockFormat::Bc5, _) => 141,     // VK_FORMAT_BC5_UNORM_BLOCK
+            (BlockFormat::Bc7, false) => 145, // VK_FORMAT_BC7_UNORM_BLOCK
+            (BlockFormat::Bc7, true) => 146,  // VK_FORMAT_BC7_SRGB_BLOCK
+        },
+    };
+}
+
+/// Builds a single sample of a data format descriptor.
+fn dfd_sample(bit_offset: u16, bit_length: u8, channel: u8, upper: u32) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+    bytes[0..2].copy_from_slice(&bit_offset.to_le_bytes());
+    bytes[2] = bit_length - 1;
+    bytes[3] = channel;
+    // Sample positions and lower bound are all zero
+    bytes[12..16].copy_from_slice(&upper.to_le_bytes());
+    return bytes;
+}
+
+/// Builds the data format descriptor for the given pixel format, including its total size.
+fn dfd(format: PixelFormat, srgb: bool, supercompressed: bool) -> Vec<u8> {
+    let alpha = match srgb {
+        true => KHR_DF_CHANNEL_ALPHA | KHR_DF_SAMPLE_DATATYPE_LINEAR,
+        false => KHR_DF_CHANNEL_ALPHA,
+    };
+
+    let (model, block_dim, samples): (u8, u8, Vec<[u8; 16]>) = match format {
+        PixelFormat::Block(block) => {
+            let samples = match block {
+                BlockFormat::Bc1 | BlockFormat::Bc4 => vec![dfd_sample(0, 64, 0, u32::MAX)],
+                BlockFormat::Bc3 => vec![
+                    dfd_sample(0, 64, alpha, u32::MAX),
+                    dfd_sample(64, 64, 0, u32::MAX),
+                ],
+                BlockFormat::Bc5 => vec![
+                    dfd_sample(0, 64, KHR_DF_CHANNEL_RED, u32::MAX),
+                    dfd_sample(64, 64, KHR_DF_CHANNEL_GREEN, u32::MAX),
+                ],
+                BlockFormat::Bc7 => vec![dfd_sample(0, 128, 0, u32::MAX)],
+            };
+            let model = match block {
+                BlockFormat::Bc1 => KHR_DF_MODEL_BC1A,
+                BlockFormat::Bc3 => KHR_DF_MODEL_BC3,
+                BlockFormat::Bc4 => KHR_DF_MODEL_BC4,
+                BlockFormat::Bc5 => KHR_DF_MODEL_BC5,
+                BlockFormat::Bc7 => KHR_DF_MODEL_BC7,
+            };
+            (model, 3, samples)
+        }
+        _ => {
+            let channels: &[u8] = match format {
+                PixelFormat::R8 | PixelFormat::R16 => &[KHR_DF_CHANNEL_RED],
+                PixelFormat::Rg8 | PixelFormat::Rg16 => &[KHR_DF_CHANNEL_RED, KHR_DF_CHANNEL_GREEN],
+                _ => &[KHR_DF_CHANNEL_RED, KHR_DF_CHANNEL_GREEN, KHR_DF_CHANNEL_BLUE, alpha],
+            };
+            let bits: u8 = match format {
+                PixelFormat::R16 | PixelFormat::Rg16 | PixelFormat::Rgba16 => 16,
+                _ => 8,
+            };
+            let samples = channels
+                .iter()
+                .enumerate()
+                .map(|(idx, channel)| {
+                    dfd_sample(idx as u16 * bits as u16, bits, *channel, (1u32 << bits) - 1)
+                })
+                .collect();
+            (KHR_DF_MODEL_RGBSDA, 0, samples)
+        }
+    };
+
+    let block_size = 24 + 16 * samples.len();
+    let mut out: Vec<u8> = Vec::with_capacity(4 + block_size);
+    out.extend_from_slice(&(4 + block_size as u32).to_le_bytes()); // dfdTotalSize
+    out.extend_from_slice(&0u32.to_le_bytes()); // vendorId and descriptorType (Khronos basic)
+    out.extend_from_slice(&2u16.to_le_bytes()); // versionNumber
+    out.extend_from_slice(&(block_size as u16).to_le_bytes()); // descriptorBlockSize
+    out.push(model);
+    out.push(1); // KHR_DF_PRIMARIES_BT709
+    out.push(if srgb { 2 } else { 1 }); // KHR_DF_TRANSFER_SRGB or KHR_DF_TRANSFER_LINEAR
+    out.push(0); // KHR_DF_FLAG_ALPHA_STRAIGHT
+    out.extend_from_slice(&[block_dim, block_dim, 0, 0]); // texelBlockDimension, minus one
+    // Supercompressed textures have no defined plane sizes
+    let bytes_plane = if supercompressed { 0 } else { format.texel_block_size() as u8 };
+    out.extend_from_slice(&[bytes_plane, 0, 0, 0, 0, 0, 0, 0]);
+    for sample in samples {
+        out.extend_from_slice(&sample);
+    }
+    return out;
+}
+
+/// Builds the key/value data, identifying IronPress as the writer.
+fn key_value_data() -> Vec<u8> {
+    let mut entry: Vec<u8> = Vec::new();
+    entry.extend_from_slice(b"KTXwriter\0");
+    entry.extend_from_slice(format!("IronPress v{0}\0", env!("CARGO_PKG_VERSION")).as_bytes());
+
+    let mut out: Vec<u8> = Vec::new();
+    out.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+    out.extend_from_slice(&entry);
+    out.resize(out.len().next_multiple_of(4), 0);
+    return out;
+}
+
+/// Encodes a KTX2 file from the encoded data of each mip level, starting at the base level.
+pub fn encode(
+    levels: &[Vec<u8>],
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    srgb: bool,
+    supercompression: Supercompression,
+) -> Vec<u8> {
+    let supercompressed = supercompression != Supercompression::None;
+    let dfd = dfd(format, srgb, supercompressed);
+    let kvd = key_value_data();
+
+    // Supercompress level data if requested
+    let payloads: Vec<Vec<u8>> = match supercompression {
+        Supercompression::None => levels.to_vec(),
+        Supercompression::Zlib => levels
+            .iter()
+            .map(|level| {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+                // Compressing into memory cannot fail, as writes to a Vec always succeed
+                encoder.write_all(level).expect("Compress level into memory");
+                encoder.finish().expect("Finish compressing level into memory")
+            })
+            .collect(),
+    };
+
+    let dfd_offset = HEADER_LENGTH + LEVEL_INDEX_LENGTH * levels.len();
+    let kvd_offset = dfd_offset + dfd.len();
+    let data_offset = kvd_offset + kvd.len();
+
+    // Level data is stored from the smallest mip to the largest, each aligned to the texel block size and 4 bytes
+    let alignment = match supercompressed {
+        true => 1,
+        false => format.texel_block_size().next_multiple_of(4),
+    };
+    let mut data: Vec<u8> = Vec::new();
+    let mut offsets: Vec<usize> = vec![0; levels.len()];
+    for (idx, payload) in payloads.iter().enumerate().rev() {
+        let offset = (data_offset + data.len()).next_multiple_of(alignment);
+        data.resize(offset - data_offset, 0);
+        offsets[idx] = offset;
+        data.extend_from_slice(payload);
+    }
+
+    let mut out: Vec<u8> = Vec::with_capacity(data_offset + data.len());
+    out.extend_from_slice(&IDENTIFIER);
+    for value in [
+        vk_format(format, srgb),
+        format.type_size(),
+        width,
+        height,
+        0, // pixelDepth
+        0, // layerCount
+        1, // faceCount
+        levels.len() as u32,
+        supercompression as u32,
+        dfd_offset as u32,
+        dfd.len() as u32,
+        kvd_offset as u32,
+        kvd.len() as u32,
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&0u64.to_le_bytes()); // sgdByteOffset
+    out.extend_from_slice(&0u64.to_le_bytes()); // sgdByteLength
+
+    for (idx, payload) in payloads.iter().enumerate() {
+        out.extend_from_slice(&(offsets[idx] as u64).to_le_bytes());
+        out.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        out.extend_from_slice(&(levels[idx].len() as u64).to_le_bytes());
+    }
+
+    out.extend_from_slice(&dfd);
+    out.extend_from_slice(&kvd);
+    out.extend_from_slice(&data);
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encode;
+    use crate::format::bcn::BlockFormat;
+    use crate::format::output::{PixelFormat, Supercompression};
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn encode_readable() {
+        // Uncompressed RGBA8, 4x2 with a full mip chain
+        let levels = vec![vec![1u8; 4 * 2 * 4], vec![2u8; 2 * 4], vec![3u8; 4]];
+        let bytes = encode(&levels, 4, 2, PixelFormat::Rgba8, true, Supercompression::None);
+        let reader = ktx2::Reader::new(bytes.as_slice()).expect("Parse KTX2 file");
+        let header = reader.header();
+        assert_eq!(header.format, Some(ktx2::Format::R8G8B8A8_SRGB));
+        assert_eq!((header.pixel_width, header.pixel_height, header.level_count), (4, 2, 3));
+        for (level, expected) in reader.levels().zip(levels.iter()) {
+            assert_eq!(level.data, expected.as_slice());
+            assert_eq!((level.data.as_ptr() as usize - bytes.as_ptr() as usize) % 4, 0);
+        }
+        let dfd = reader.dfd_blocks().next().expect("Find DFD block");
+        let basic = ktx2::DfdBlockBasic::parse(dfd.data).expect("Parse DFD block");
+        assert_eq!(basic.header.transfer_function, Some(ktx2::TransferFunction::SRGB));
+        assert_eq!(basic.sample_information().count(), 4);
+
+        // Zlib supercompressed BC5
+        let levels = vec![vec![7u8; 16]];
+        let bytes = encode(&levels, 4, 4, PixelFormat::Block(BlockFormat::Bc5), false, Supercompression::Zlib);
+        let reader = ktx2::Reader::new(bytes.as_slice()).expect("Parse KTX2 file");
+        assert_eq!(reader.header().format, Some(ktx2::Format::BC5_UNORM_BLOCK));
+        assert_eq!(reader.header().supercompression_scheme, Some(ktx2::SupercompressionScheme::ZLIB));
+        let level = reader.levels().next().expect("Find base level");
+        assert_eq!(level.uncompressed_byte_length, 16);
+        let mut decoded: Vec<u8> = Vec::new();
+        ZlibDecoder::new(level.data)
+            .read_to_end(&mut decoded)
+            .expect("Decompress level");
+        assert_eq!(decoded, levels[0]);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::error::{Error, Result};
+use crate::format::bcn::{self, BlockFormat};
+use crate::format::{dds, ktx2, png};
+use crate::op::mip::{self, MipFilter};
+use crate::op::normal::{self, NormalLayout};
+use image::{ColorType, DynamicImage, GenericImageView, Rgba, RgbaImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File formats that textures can be written out as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Container {
+    Png,
+    Ktx2,
+    Dds,
+}
+
+/// How texel data is stored within a container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Uncompressed texels.
+    Raw,
+    /// GPU block compression, chosen from the color format of the map.
+    Bcn,
+}
+
+/// Lossless compression applied on top of the texel data of a container.
+/// Values match the KTX2 supercompression scheme identifiers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Supercompression {
+    None = 0,
+    Zlib = 3,
+}
+
+/// Describes how texture maps are written out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputFormat {
+    pub container: Container,
+    /// Texel encoding, or `None` to use the default of the container. See [`OutputFormat::encoding`].
+    pub encoding: Option<Encoding>,
+    pub supercompression: Supercompression,
+    /// Whether to include a full mip chain, or `None` to use the default of the container. See [`OutputFormat::mips`].
+    pub mips: Option<bool>,
+    /// Whether to use BC7 rather than BC1 or BC3 when block compressing color maps.
+    pub bc7: bool,
+    /// Channel layout of normal maps.
+    pub normals: NormalLayout,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        return OutputFormat {
+            container: Container::Png,
+            encoding: None,
+            supercompression: Supercompression::None,
+            mips: None,
+            bc7: false,
+            normals: NormalLayout::Xyz,
+        };
+    }
+}
+
+impl OutputFormat {
+    /// Parses an output format from either a container name (`"ktx2"`),
+    /// or an object of the form `{ "container": "ktx2", "encoding": "bcn", "supercompression": "zlib", "mips": true, "bc7": false, "normals": "xyz" }`.
+    /// Any unspecified settings are inherited from `base`. Returns `None` if the value is malformed.
+    pub fn from_json(value: &json::JsonValue, base: OutputFormat) -> Option<OutputFormat> {
+        let mut format = base;
+
+        if let Some(container) = value.as_str() {
+            format.container = parse_container(container)?;
+            return Some(format);
+        }
+
+        if !value.is_object() {
+            return None;
+        }
+        if value.has_key("container") {
+            format.container = parse_container(value["container"].as_str()?)?;
+        }
+        if value.has_key("encoding") {
+            format.encoding = match value["encoding"].as_str()? {
+                "raw" | "uncompressed" => Some(Encoding::Raw),
+                "bcn" | "bc" => Some(Encoding::Bcn),
+                _ => return None,
+            };
+        }
+        if value.has_key("supercompression") {
+            format.supercompression = match value["supercompression"].as_str()? {
+                "none" => Supercompression::None,
+                "zlib" => Supercompression::Zlib,
+                _ => return None,
+            };
+        }
+        if value.has_key("mips") {
+            format.mips = Some(value["mips"].as_bool()?);
+        }
+        if value.has_key("bc7") {
+            format.bc7 = value["bc7"].as_bool()?;
+        }
+        if value.has_key("normals") {
+            format.normals = NormalLayout::parse(value["normals"].as_str()?)?;
+        }
+        return Some(format);
+    }
+
+    /// Returns the texel encoding to use. Unless specified, DDS is block compressed and other containers are uncompressed.
+    pub fn encoding(&self) -> Encoding {
+        return match (self.encoding, self.container) {
+            (Some(encoding), _) => encoding,
+            (None, Container::Dds) => Encoding::Bcn,
+            (None, _) => Encoding::Raw,
+        };
+    }
+
+    /// Returns true if a mip chain should be written. Unless specified, GPU containers include mips and PNG does not.
+    /// For PNG, each mip level past the base is written as its own `_mipN` file.
+    pub fn mips(&self) -> bool {
+        return self.mips.unwrap_or(self.container != Container::Png);
+    }
+
+    /// Returns the file extension for this format.
+    pub fn extension(&self) -> &'static str {
+        return match self.container {
+            Container::Png => "png",
+            Container::Ktx2 => "ktx2",
+            Container::Dds => "dds",
+        };
+    }
+}
+
+/// Parses a container name.
+fn parse_container(name: &str) -> Option<Container> {
+    return match name {
+        "png" => Some(Container::Png),
+        "ktx2" => Some(Container::Ktx2),
+        "dds" => Some(Container::Dds),
+        _ => None,
+    };
+}
+
+/// Texel layouts that GPU containers store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    R8,
+    Rg8,
+    Rgba8,
+    R16,
+    Rg16,
+    Rgba16,
+    Block(BlockFormat),
+}
+
+impl PixelFormat {
+    /// Returns the number of bytes per texel, or per block for block compressed formats.
+    pub fn texel_block_size(&self) -> usize {
+        return match self {
+            PixelFormat::R8 => 1,
+            PixelFormat::Rg8 | PixelFormat::R16 => 2,
+            PixelFormat::Rgba8 | PixelFormat::Rg16 => 4,
+            PixelFormat::Rgba16 => 8,
+            PixelFormat::Block(block) => block.block_size(),
+        };
+    }
+
+    /// Returns true if this format has an sRGB encoded variant.
+    pub fn supports_srgb(&self) -> bool {
+        return match self {
+            PixelFormat::R8 | PixelFormat::Rg8 | PixelFormat::Rgba8 => true,
+            PixelFormat::R16 | PixelFormat::Rg16 | PixelFormat::Rgba16 => false,
+            PixelFormat::Block(block) => {
+                matches!(block, BlockFormat::Bc1 | BlockFormat::Bc3 | BlockFormat::Bc7)
+            }
+        };
+    }
+
+    /// Returns the size in bytes of the basic data type of this format.
+    pub fn type_size(&self) -> u32 {
+        return match self {
+            PixelFormat::R16 | PixelFormat::Rg16 | PixelFormat::Rgba16 => 2,
+            _ => 1,
+        };
+    }
+}
+
+/// Chooses the GPU pixel format for a map of the given color format (see [`crate::util::map_to_color`]).
+/// Block compression uses BC4 for single channel maps, BC5 for two channel maps and normals,
+/// and BC1 for color or BC3 for color with alpha (or BC7 for both, if requested).
+pub fn pixel_format(ct: ColorType, format: &OutputFormat) -> PixelFormat {
+    return match format.encoding() {
+        Encoding::Raw => match ct {
+            ColorType::L8 => PixelFormat::R8,
+            ColorType::La8 => PixelFormat::Rg8,
+            ColorType::L16 => PixelFormat::R16,
+            ColorType::La16 => PixelFormat::Rg16,
+            ColorType::Rgb16 | ColorType::Rgba16 => PixelFormat::Rgba16,
+            _ => PixelFormat::Rgba8,
+        },
+        Encoding::Bcn => match ct {
+            ColorType::L8 | ColorType::L16 => PixelFormat::Block(BlockFormat::Bc4),
+            ColorType::La8 | ColorType::La16 | ColorType::Rgb16 => PixelFormat::Block(BlockFormat::Bc5),
+            _ if format.bc7 => PixelFormat::Block(BlockFormat::Bc7),
+            ColorType::Rgba8 | ColorType::Rgba16 => PixelFormat::Block(BlockFormat::Bc3),
+            _ => PixelFormat::Block(BlockFormat::Bc1),
+        },
+    };
+}
+
+/// Converts an image to 8-bit RGBA for block compression.
+/// Two channel images are stored in red and green, rather than being expanded as grayscale.
+fn block_source(img: &DynamicImage) -> RgbaImage {
+    if img.color().channel_count() == 2 {
+        let la = img.to_luma_alpha8();
+        return RgbaImage::from_fn(la.width(), la.height(), |x, y| {
+            let px = la.get_pixel(x, y).0;
+            Rgba([px[0], px[1], 0, 255])
+        });
+    }
+    return img.to_rgba8();
+}
+
+/// Encodes a single image into the texel data of the given pixel format.
+pub fn encode_level(img: &DynamicImage, format: PixelFormat) -> Vec<u8> {
+    return match format {
+        PixelFormat::R8 => img.to_luma8().into_raw(),
+        PixelFormat::Rg8 => img.to_luma_alpha8().into_raw(),
+        PixelFormat::Rgba8 => img.to_rgba8().into_raw(),
+        PixelFormat::R16 => img.to_luma16().iter().flat_map(|v| v.to_le_bytes()).collect(),
+        PixelFormat::Rg16 => img.to_luma_alpha16().iter().flat_map(|v| v.to_le_bytes()).collect(),
+        PixelFormat::Rgba16 => img.to_rgba16().iter().flat_map(|v| v.to_le_bytes()).collect(),
+        PixelFormat::Block(block) => bcn::compress(&block_source(img), block),
+    };
+}
+
+/// Returns the path of a mip level written as its own file, by appending `_mipN` to the file stem of the base level.
+pub fn mip_path(path: &Path, level: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+    let extension = path.extension().unwrap().to_str().unwrap();
+    return path.with_file_name(format!("{0}_mip{1}.{2}", stem, level, extension));
+}
+
+/// Saves an image to the given path in the given output format, downsampling mip levels with `filter`.
+/// `ct` is the color format of the map, and `srgb` marks whether the color data is sRGB encoded.
+/// If `is_normal` is set, the image is a normal map stored in the normal layout of the format, after mips are generated from the full vectors.
+pub fn save(
+    path: &Path,
+    img: DynamicImage,
+    mut ct: ColorType,
+    srgb: bool,
+    format: &OutputFormat,
+    filter: MipFilter,
+    is_normal: bool,
+) -> Result<()> {
+    let (width, height) = img.dimensions();
+    let mut levels = match format.mips() {
+        true => mip::mip_chain(img, filter, srgb),
+        false => vec![img],
+    };
+    if is_normal {
+        ct = format.normals.color_type();
+        levels = levels
+            .into_iter()
+            .map(|level| normal::encode_layout(level.into_rgb16(), format.normals))
+            .collect();
+    }
+
+    if format.container == Container::Png {
+        for (i, level) in levels.iter().enumerate() {
+            let level_path = if i == 0 { path.to_path_buf() } else { mip_path(path, i) };
+            let (w, h) = level.dimensions();
+            png::compressed_save(level_path.as_path(), level.as_bytes(), w, h, ct.into())?;
+        }
+        return Ok(());
+    }
+
+    // GPU containers
+    let pixel_format = pixel_format(ct, format);
+    let srgb = srgb && pixel_format.supports_srgb();
+    let data: Vec<Vec<u8>> = levels
+        .iter()
+        .map(|level| encode_level(level, pixel_format))
+        .collect();
+
+    let bytes = match format.container {
+        Container::Ktx2 => {
+            ktx2::encode(&data, width, height, pixel_format, srgb, format.supercompression)
+        }
+        _ => dds::encode(&data, width, height, pixel_format, srgb),
+    };
+    return fs::write(path, bytes).map_err(|source| Error::Write { path: path.to_path_buf(), source });
+}
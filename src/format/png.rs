@@ -0,0 +1,31 @@
+use crate::error::{Error, Result};
+use image::codecs::png;
+use image::{ExtendedColorType, ImageEncoder};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Saves an image buffer to the given path, using a specific color format, at the best compression
+pub fn compressed_save(
+    path: &Path,
+    buffer: &[u8],
+    width: u32,
+    height: u32,
+    format: ExtendedColorType,
+) -> Result<()> {
+    // Create a file at the given path
+    let f = File::create(path).map_err(|source| Error::Write { path: path.to_path_buf(), source })?;
+    let writer = BufWriter::new(f); // Create a writer buffer to it
+
+    // Set up a PNG encoder on top of the buffer, and attempt to maximize compression (for space efficiency)
+    let encoder = png::PngEncoder::new_with_quality(
+        writer,
+        png::CompressionType::Best,
+        png::FilterType::Adaptive,
+    );
+
+    // Finally, write out the image
+    return encoder
+        .write_image(buffer, width, height, format)
+        .map_err(|source| Error::Encode { path: path.to_path_buf(), source });
+}
@@ -0,0 +1,172 @@
+use crate::format::bcn::BlockFormat;
+use crate::format::output::PixelFormat;
+use crate::format::{dds, ktx2};
+use image::{ImageDecoder, ImageReader};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// Identifier at the start of every KTX2 file.
+const KTX2_MAGIC: &[u8] = b"\xABKTX 20\xBB\r\n\x1A\n";
+/// Identifier at the start of every DDS file.
+const DDS_MAGIC: &[u8] = b"DDS ";
+
+/// Every pixel format that IronPress can write to a GPU container.
+const PIXEL_FORMATS: [PixelFormat; 11] = [
+    PixelFormat::R8,
+    PixelFormat::Rg8,
+    PixelFormat::Rgba8,
+    PixelFormat::R16,
+    PixelFormat::Rg16,
+    PixelFormat::Rgba16,
+    PixelFormat::Block(BlockFormat::Bc1),
+    PixelFormat::Block(BlockFormat::Bc3),
+    PixelFormat::Block(BlockFormat::Bc4),
+    PixelFormat::Block(BlockFormat::Bc5),
+    PixelFormat::Block(BlockFormat::Bc7),
+];
+
+/// Reads a little-endian u32 from the given byte offset, if the data is long enough.
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let data = bytes.get(offset..offset + 4)?;
+    return Some(u32::from_le_bytes(data.try_into().unwrap()));
+}
+
+/// Describes a format identifier, using the given mapping from pixel formats to identifiers.
+fn describe_format(id: u32, mapping: fn(PixelFormat, bool) -> u32) -> String {
+    for format in PIXEL_FORMATS {
+        if mapping(format, false) == id {
+            return format!("{0:?}", format);
+        }
+        if format.supports_srgb() && mapping(format, true) == id {
+            return format!("{0:?} sRGB", format);
+        }
+    }
+    return format!("unknown format {0}", id);
+}
+
+/// Describes the header of a KTX2 file.
+fn describe_ktx2(bytes: &[u8]) -> Option<String> {
+    if !bytes.starts_with(KTX2_MAGIC) {
+        return None;
+    }
+    let supercompression = match read_u32(bytes, 44)? {
+        0 => "",
+        3 => ", zlib",
+        _ => ", supercompressed",
+    };
+    return Some(format!(
+        "KTX2 {0}x{1}, {2}, {3} mip level(s){4}",
+        read_u32(bytes, 20)?,
+        read_u32(bytes, 24)?,
+        describe_format(read_u32(bytes, 12)?, ktx2::vk_format),
+        read_u32(bytes, 40)?.max(1),
+        supercompression
+    ));
+}
+
+/// Describes the header of a DDS file.
+fn describe_dds(bytes: &[u8]) -> Option<String> {
+    if !bytes.starts_with(DDS_MAGIC) {
+        return None;
+    }
+    let four_cc = bytes.get(84..88)?;
+    let format = match four_cc {
+        b"DX10" => describe_format(read_u32(bytes, 128)?, dds::dxgi_format),
+        _ => format!("FourCC {0}", String::from_utf8_lossy(four_cc)),
+    };
+    return Some(format!(
+        "DDS {0}x{1}, {2}, {3} mip level(s)",
+        read_u32(bytes, 16)?,
+        read_u32(bytes, 12)?,
+        format,
+        read_u32(bytes, 28)?.max(1)
+    ));
+}
+
+/// Describes an image file that the image crate can read, without decoding its pixels.
+fn describe_image(path: &Path) -> Result<String, String> {
+    let reader = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|err| err.to_string())?;
+    let format = reader.format();
+    let decoder = reader.into_decoder().map_err(|err| err.to_string())?;
+    let (width, height) = decoder.dimensions();
+    return Ok(format!(
+        "{0} {1}x{2}, {3:?}",
+        format.map_or("Image".to_string(), |f| format!("{0:?}", f).to_uppercase()),
+        width,
+        height,
+        decoder.color_type()
+    ));
+}
+
+/// Describes a texture file.
+fn describe(path: &Path) -> Result<String, String> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase();
+    if extension == "ktx2" || extension == "dds" {
+        let bytes = fs::read(path).map_err(|err| err.to_string())?;
+        let description = match extension.as_str() {
+            "ktx2" => describe_ktx2(&bytes),
+            _ => describe_dds(&bytes),
+        };
+        return description.ok_or_else(|| "malformed header".to_string());
+    }
+    return describe_image(path);
+}
+
+/// Returns the dimensions of a texture file, reading only its header.
+pub fn dimensions(path: &Path) -> Option<(u32, u32)> {
+    let mut bytes: Vec<u8> = Vec::new();
+    File::open(path).ok()?.take(32).read_to_end(&mut bytes).ok()?;
+    if bytes.starts_with(KTX2_MAGIC) {
+        return Some((read_u32(&bytes, 20)?, read_u32(&bytes, 24)?));
+    }
+    if bytes.starts_with(DDS_MAGIC) {
+        return Some((read_u32(&bytes, 16)?, read_u32(&bytes, 12)?));
+    }
+    return image::image_dimensions(path).ok();
+}
+
+/// Prints the dimensions and pixel format of each texture file.
+/// Returns TRUE if every file could be read, FALSE otherwise.
+pub fn from_files(files: &[String]) -> bool {
+    let mut success = true;
+    for file in files {
+        match describe(Path::new(file)) {
+            Ok(description) => println!("{0}: {1}", file, description),
+            Err(err) => {
+                eprintln!("{0}: failed to inspect, {1}", file, err);
+                success = false;
+            }
+        }
+    }
+    return success;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe_dds, describe_ktx2, dimensions};
+    use crate::format::bcn::BlockFormat;
+    use crate::format::output::{PixelFormat, Supercompression};
+    use crate::format::{dds, ktx2};
+    use crate::util::test_dir;
+    use std::fs;
+
+    #[test]
+    fn describe_headers() {
+        let levels = vec![vec![0u8; 128], vec![0u8; 32]];
+        let bytes = ktx2::encode(&levels, 8, 4, PixelFormat::Rgba8, true, Supercompression::Zlib);
+        assert_eq!(describe_ktx2(&bytes).unwrap(), "KTX2 8x4, Rgba8 sRGB, 2 mip level(s), zlib");
+
+        let levels = vec![vec![0u8; 64]];
+        let bytes = dds::encode(&levels, 8, 8, PixelFormat::Block(BlockFormat::Bc5), false);
+        assert_eq!(describe_dds(&bytes).unwrap(), "DDS 8x8, Block(Bc5), 1 mip level(s)");
+
+        assert!(describe_dds(b"PNG").is_none());
+
+        let path = test_dir("describe_headers", &[]).join("texture.dds");
+        fs::write(&path, &bytes).expect("Write test texture");
+        assert_eq!(dimensions(&path), Some((8, 8)));
+    }
+}
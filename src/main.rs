@@ -7,31 +7,94 @@ mod cli {
     pub mod args;
     /// Configuration file formatting.
     pub mod config;
+    /// Output verbosity.
+    pub mod verbosity;
 }
 /// Common utilities.
 mod util;
+/// Texture file formats.
+mod format {
+    /// Block compression encoders.
+    pub mod bcn;
+    /// DDS container writer.
+    pub mod dds;
+    /// KTX2 container writer.
+    pub mod ktx2;
+    /// Output format selection and saving.
+    pub mod output;
+    /// PNG writer.
+    pub mod png;
+}
 /// Operations for manipulating images.
 mod op {
     /// Methods for flipping channels.
     pub mod flip;
+    /// Methods for generating mipmaps.
+    pub mod mip;
+    /// Methods for resampling and renormalizing normal maps.
+    pub mod normal;
     /// Methods for packing channels.
     pub mod pack;
+    /// Methods for unpacking channels.
+    pub mod unpack;
 }
+/// GPU memory estimates and budgets.
+mod budget;
+/// Build manifest for skipping unchanged textures.
+mod cache;
+/// Output cleaning command.
+mod clean;
+/// Material discovery from source textures.
+mod discover;
+/// Errors that can occur while building textures.
+mod error;
+/// Texture inspection command.
+mod inspect;
+/// Typed pipeline configuration, parsed and validated from JSON.
+mod model;
+/// File name conventions of source and output textures.
+mod naming;
 /// Texture pipeline command.
 mod pipeline;
+/// Worker pool for processing textures.
+mod pool;
+/// Machine-readable build reports.
+mod report;
+/// Pipeline validation command.
+mod validate;
+/// Pipeline watch command.
+mod watch;
 
-use std::{path::Path, process::exit};
-use clap::Parser;
+use std::{path::Path, process::exit, time::Duration};
+use clap::{CommandFactory, Parser};
+use cli::args::Command;
 
 fn main() {
     // Parse command-line arguments via clap.
     let args = cli::args::CLIArguments::parse();
+    cli::verbosity::set(args.quiet, args.verbose);
 
-    if args.default {
-        let success = cli::config::write_default(Path::new(&args.file));
-        exit(!success as i32);
-    }
-
-    // Perform pipeline.
-    pipeline::from_file(Path::new(&args.file), args.dryrun);
+    let success = match args.command {
+        Some(Command::Build { file, options }) => pipeline::from_file(Path::new(&file), &options),
+        Some(Command::Watch { file, options, debounce }) => {
+            watch::from_file(Path::new(&file), &options, Duration::from_millis(debounce))
+        }
+        Some(Command::Init { file, force, scan }) => cli::config::write_default(Path::new(&file), scan.as_deref(), force),
+        Some(Command::Validate { file }) => validate::from_file(Path::new(&file)),
+        Some(Command::Inspect { files }) => inspect::from_files(&files),
+        Some(Command::Clean { file, output }) => clean::from_file(Path::new(&file), output.as_ref()),
+        None => {
+            // `ironpress <FILE>` is the same as `ironpress build <FILE>`
+            let Some(file) = args.file else {
+                cli::args::CLIArguments::command().print_help().unwrap();
+                exit(2);
+            };
+            if args.default {
+                cli::config::write_default(Path::new(&file), None, true)
+            } else {
+                pipeline::from_file(Path::new(&file), &args.build)
+            }
+        }
+    };
+    exit(!success as i32);
 }
@@ -0,0 +1,1262 @@
+use crate::budget::BudgetAction;
+use crate::cli::verbosity;
+use crate::format::output::OutputFormat;
+use crate::naming::{self, Naming};
+use crate::{discover, op, util};
+use crate::op::mip::MipFilter;
+use crate::op::normal::NormalLayout;
+use crate::op::pack::PackLayout;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// Keys allowed at the top level of a pipeline file.
+const PIPELINE_KEYS: [&str; 18] = [
+    "input",
+    "output",
+    "flip_normals",
+    "renormalize_normals",
+    "normal_source",
+    "format",
+    "naming",
+    "layouts",
+    "unpack",
+    "color_spaces",
+    "mip_filters",
+    "gpu_budget",
+    "budget_action",
+    "profiles",
+    "globals",
+    "templates",
+    "discover",
+    "materials",
+];
+
+/// Keys allowed in `globals`.
+const GLOBALS_KEYS: [&str; 16] = [
+    "max_dimension",
+    "scale",
+    "res_base",
+    "alpha",
+    "flip_normals",
+    "renormalize_normals",
+    "format",
+    "naming",
+    "normal_source",
+    "layouts",
+    "unpack",
+    "color_spaces",
+    "mip_filters",
+    "gpu_budget",
+    "budget_action",
+    "channels",
+];
+
+/// Keys allowed in a material or template.
+const MATERIAL_KEYS: [&str; 17] = [
+    "extends",
+    "max_dimension",
+    "scale",
+    "res_base",
+    "alpha",
+    "flip_normals",
+    "renormalize_normals",
+    "format",
+    "naming",
+    "normal_source",
+    "layouts",
+    "unpack",
+    "color_spaces",
+    "mip_filters",
+    "gpu_budget",
+    "budget_action",
+    "channels",
+];
+
+/// Keys allowed in a profile.
+const PROFILE_KEYS: [&str; 4] = ["output", "max_dimension", "format", "materials"];
+
+/// Keys allowed in a material override of a profile.
+const PROFILE_MATERIAL_KEYS: [&str; 2] = ["max_dimension", "format"];
+
+/// Keys allowed in a naming object.
+const NAMING_KEYS: [&str; 5] = ["input", "output", "aliases", "suffixes", "extensions"];
+
+/// Keys allowed in an output format object.
+const FORMAT_KEYS: [&str; 6] = ["container", "encoding", "supercompression", "mips", "bc7", "normals"];
+
+/// Problems found while parsing a pipeline file, each prefixed with the JSON path it was found at.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    /// Problems that prevent the pipeline from running.
+    pub errors: Vec<String>,
+    /// Problems that are ignored, such as unknown keys.
+    pub warnings: Vec<String>,
+}
+
+impl Diagnostics {
+    /// Records an error at the given JSON path, unless it was already recorded.
+    pub fn error(&mut self, path: &str, message: &str) {
+        let error = format!("{0}: {1}", path, message);
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    /// Records a warning at the given JSON path, unless it was already recorded.
+    pub fn warn(&mut self, path: &str, message: &str) {
+        let warning = format!("{0}: {1}", path, message);
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Prints all warnings and errors. Warnings are not printed if output is quiet.
+    pub fn print(&self) {
+        if !verbosity::is_quiet() {
+            for warning in self.warnings.iter() {
+                println!("\twarning: {0}", warning);
+            }
+        }
+        for error in self.errors.iter() {
+            eprintln!("\terror: {0}", error);
+        }
+    }
+}
+
+/// A single texture map to output for a material.
+#[derive(Clone, Debug)]
+pub struct ChannelConfig {
+    /// Name of the map, such as `basecolor`.
+    pub name: String,
+    /// Packing layout to use, if this channel is a packed texture.
+    pub layout: Option<PackLayout>,
+    /// Filter for downsampling mip levels.
+    pub mip_filter: MipFilter,
+}
+
+/// A material, with the settings it inherits already merged in.
+#[derive(Clone, Debug)]
+pub struct MaterialConfig {
+    pub name: String,
+    /// Maximum dimension of output textures.
+    pub max_dimension: u32,
+    /// Resolution the source textures were authored at, if declared.
+    pub res_base: Option<u32>,
+    /// Whether to include alpha in the basecolor map.
+    pub alpha: bool,
+    /// Whether to invert the green channel of normal maps.
+    pub flip_normals: bool,
+    /// Whether to renormalize normal map vectors.
+    pub renormalize_normals: bool,
+    pub format: OutputFormat,
+    /// File name conventions of source and output textures.
+    pub naming: Naming,
+    /// Channel layout of source normal maps.
+    pub normal_source: NormalLayout,
+    /// Packed source textures, and their layouts, that source maps can be unpacked from.
+    pub unpack: Vec<(String, PackLayout)>,
+    /// Color space overrides by map name, where `true` is sRGB.
+    pub color_spaces: HashMap<String, bool>,
+    /// GPU memory budget of all textures of the material, in bytes.
+    pub gpu_budget: Option<u64>,
+    /// What to do if the material exceeds its GPU memory budget.
+    pub budget_action: BudgetAction,
+    pub channels: Vec<ChannelConfig>,
+}
+
+impl MaterialConfig {
+    /// Returns the name of every map the material may read a source texture of,
+    /// including the sources of packed textures and declared packed sources.
+    pub fn source_maps(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for channel in self.channels.iter() {
+            names.push(&channel.name);
+            let slots = channel.layout.iter().flat_map(|layout| layout.slots.iter().flatten());
+            names.extend(slots.filter_map(|slot| slot.map.as_deref()));
+        }
+        names.extend(self.unpack.iter().map(|(source, _)| source.as_str()));
+
+        let mut maps: Vec<&str> = Vec::new();
+        for name in names {
+            if !maps.contains(&name) {
+                maps.push(name);
+            }
+        }
+        return maps;
+    }
+
+    /// Returns true if the given map stores sRGB encoded color, unless overridden by the pipeline.
+    pub fn is_srgb(&self, map: &str) -> bool {
+        return self.color_spaces.get(map).copied().unwrap_or_else(|| util::map_is_srgb(map));
+    }
+}
+
+/// A named set of overrides for building the pipeline for a specific platform.
+#[derive(Clone, Debug)]
+pub struct ProfileConfig {
+    pub name: String,
+    /// Output directory, relative to the pipeline file.
+    pub output: String,
+    /// Materials, with the overrides of the profile already applied.
+    pub materials: Vec<MaterialConfig>,
+}
+
+/// A parsed and validated IronPress pipeline file.
+#[derive(Clone, Debug)]
+pub struct PipelineConfig {
+    /// Input directory, relative to the pipeline file.
+    pub input: String,
+    /// Output directory, relative to the pipeline file.
+    pub output: String,
+    /// GPU memory budget of all textures of the pipeline, in bytes.
+    pub gpu_budget: Option<u64>,
+    /// What to do if the pipeline exceeds its GPU memory budget.
+    pub budget_action: BudgetAction,
+    pub materials: Vec<MaterialConfig>,
+    pub profiles: Vec<ProfileConfig>,
+}
+
+impl PipelineConfig {
+    /// Returns the pipeline as built for the given profile, or `None` if there is no such profile.
+    pub fn with_profile(&self, name: &str) -> Option<PipelineConfig> {
+        let profile = self.profiles.iter().find(|profile| profile.name == name)?;
+        return Some(PipelineConfig {
+            output: profile.output.clone(),
+            materials: profile.materials.clone(),
+            profiles: Vec::new(),
+            ..self.clone()
+        });
+    }
+}
+
+/// A packed source texture's layout, either by name or declared inline.
+#[derive(Clone)]
+enum UnpackRef {
+    Named(String),
+    Inline(PackLayout),
+}
+
+/// Joins a key onto a JSON path.
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        return key.to_string();
+    }
+    return format!("{0}.{1}", path, key);
+}
+
+/// Warns about any keys of an object that are not in the list of known keys.
+fn check_keys(value: &json::JsonValue, known: &[&str], path: &str, diag: &mut Diagnostics) {
+    for (key, _) in value.entries() {
+        if !known.contains(&key) {
+            diag.warn(&join(path, key), "unknown key, ignoring");
+        }
+    }
+}
+
+/// Reads an optional boolean, recording an error if it is the wrong type.
+fn parse_bool(value: &json::JsonValue, path: &str, default: bool, diag: &mut Diagnostics) -> bool {
+    if value.is_null() {
+        return default;
+    }
+    return value.as_bool().unwrap_or_else(|| {
+        diag.error(path, "expected boolean");
+        default
+    });
+}
+
+/// Reads a required string, recording an error if it is missing or the wrong type.
+fn parse_string(value: &json::JsonValue, path: &str, diag: &mut Diagnostics) -> Option<String> {
+    if value.is_null() {
+        diag.error(path, "missing required string");
+        return None;
+    }
+    let Some(string) = value.as_str() else {
+        diag.error(path, "expected string");
+        return None;
+    };
+    return Some(string.to_string());
+}
+
+/// Reads an optional positive integer, recording an error if it is the wrong type.
+fn parse_dimension(value: &json::JsonValue, path: &str, diag: &mut Diagnostics) -> Option<u32> {
+    if value.is_null() {
+        return None;
+    }
+    return match value.as_u32() {
+        Some(0) => {
+            diag.error(path, "expected integer greater than zero");
+            None
+        }
+        Some(dimension) => Some(dimension),
+        None => {
+            diag.error(path, "expected integer");
+            None
+        }
+    };
+}
+
+/// Reads an optional scale factor, greater than zero and at most one, recording an error if it is malformed.
+fn parse_scale(value: &json::JsonValue, path: &str, diag: &mut Diagnostics) -> Option<f64> {
+    if value.is_null() {
+        return None;
+    }
+    return match value.as_f64() {
+        Some(scale) if scale > 0.0 && scale <= 1.0 => Some(scale),
+        _ => {
+            diag.error(path, "expected number greater than 0 and at most 1");
+            None
+        }
+    };
+}
+
+/// Reads an optional size in bytes, either as an integer or a string with a binary unit such as `"64MiB"`.
+/// Records an error if it is malformed.
+fn parse_bytes(value: &json::JsonValue, path: &str, diag: &mut Diagnostics) -> Option<u64> {
+    if value.is_null() {
+        return None;
+    }
+    if let Some(bytes) = value.as_u64() {
+        return Some(bytes);
+    }
+    let size = value.as_str().and_then(|size| {
+        let size = size.trim();
+        let split = size.find(|c: char| !c.is_ascii_digit()).unwrap_or(size.len());
+        let multiplier: u64 = match size[split..].trim() {
+            "" | "B" => 1,
+            "KiB" => 1 << 10,
+            "MiB" => 1 << 20,
+            "GiB" => 1 << 30,
+            _ => return None,
+        };
+        size[..split].parse::<u64>().ok()?.checked_mul(multiplier)
+    });
+    if size.is_none() {
+        diag.error(path, "expected number of bytes, or a size such as \"64MiB\"");
+    }
+    return size;
+}
+
+/// Parses a budget action name.
+fn parse_budget_action(value: &json::JsonValue, base: BudgetAction, path: &str, diag: &mut Diagnostics) -> BudgetAction {
+    if value.is_null() {
+        return base;
+    }
+    return value.as_str().and_then(BudgetAction::parse).unwrap_or_else(|| {
+        diag.error(path, "expected \"warn\" or \"downscale\"");
+        base
+    });
+}
+
+/// Reads an optional object, recording an error if it is the wrong type.
+fn expect_object(value: &json::JsonValue, path: &str, diag: &mut Diagnostics) -> bool {
+    if value.is_null() {
+        return false;
+    }
+    if !value.is_object() {
+        diag.error(path, "expected object");
+        return false;
+    }
+    return true;
+}
+
+/// Parses an output format, inheriting unspecified settings from the base format.
+fn parse_format(value: &json::JsonValue, base: OutputFormat, path: &str, diag: &mut Diagnostics) -> OutputFormat {
+    if value.is_null() {
+        return base;
+    }
+    if value.is_object() {
+        check_keys(value, &FORMAT_KEYS, path, diag);
+    }
+    return OutputFormat::from_json(value, base).unwrap_or_else(|| {
+        diag.error(path, "malformed output format");
+        base
+    });
+}
+
+/// Reads a file name template, recording an error if it is malformed or uses unknown placeholders.
+fn parse_template(value: &json::JsonValue, placeholders: &[&str], path: &str, diag: &mut Diagnostics) -> Option<String> {
+    let Some(template) = value.as_str() else {
+        diag.error(path, "expected file name template");
+        return None;
+    };
+    if template.is_empty() || template.contains(['/', '\\']) {
+        diag.error(path, "expected file name, without folders or extension");
+        return None;
+    }
+    let unknown = naming::unknown_placeholders(template, placeholders);
+    if !unknown.is_empty() {
+        diag.error(path, &format!("unknown placeholders {0:?}, expected one of {1:?}", unknown, placeholders));
+        return None;
+    }
+    return Some(template.to_string());
+}
+
+/// Parses file name conventions, inheriting unspecified settings from the base conventions.
+/// Aliases and suffixes are merged by map name.
+fn parse_naming(value: &json::JsonValue, base: &Naming, path: &str, diag: &mut Diagnostics) -> Naming {
+    let mut naming = base.clone();
+    if !expect_object(value, path, diag) {
+        return naming;
+    }
+    check_keys(value, &NAMING_KEYS, path, diag);
+
+    // Source file names can be a single template, or several to try in order
+    let input_path = join(path, "input");
+    if value["input"].is_array() {
+        naming.inputs = value["input"]
+            .members()
+            .enumerate()
+            .filter_map(|(i, member)| {
+                parse_template(member, &naming::INPUT_PLACEHOLDERS, &format!("{0}[{1}]", input_path, i), diag)
+            })
+            .collect();
+        if naming.inputs.is_empty() {
+            diag.error(&input_path, "expected at least one file name template");
+        }
+    } else if !value["input"].is_null() {
+        naming.inputs = parse_template(&value["input"], &naming::INPUT_PLACEHOLDERS, &input_path, diag).into_iter().collect();
+    }
+    if !value["output"].is_null() {
+        let output = parse_template(&value["output"], &naming::OUTPUT_PLACEHOLDERS, &join(path, "output"), diag);
+        naming.output = output.unwrap_or(naming.output);
+    }
+
+    let aliases_path = join(path, "aliases");
+    if expect_object(&value["aliases"], &aliases_path, diag) {
+        for (map, entry) in value["aliases"].entries() {
+            // A single alias can be given as a string
+            let aliases: Option<Vec<String>> = match entry.as_str() {
+                Some(alias) => Some(vec![alias.to_string()]),
+                None if entry.is_array() => entry.members().map(|alias| alias.as_str().map(str::to_string)).collect(),
+                None => None,
+            };
+            match aliases {
+                Some(aliases) => {
+                    naming.aliases.insert(map.to_string(), aliases);
+                }
+                None => diag.error(&join(&aliases_path, map), "expected name or array of names"),
+            }
+        }
+    }
+
+    // Source extensions replace inherited ones, as they are in order of priority
+    let extensions_path = join(path, "extensions");
+    if !value["extensions"].is_null() {
+        if !value["extensions"].is_array() {
+            diag.error(&extensions_path, "expected array of file extensions");
+        }
+        let mut extensions: Vec<String> = Vec::new();
+        for (i, member) in value["extensions"].members().enumerate() {
+            let format = member.as_str().and_then(image::ImageFormat::from_extension);
+            match format.filter(|format| format.reading_enabled()) {
+                Some(_) => extensions.push(member.as_str().unwrap().to_string()),
+                None => diag.error(&format!("{0}[{1}]", extensions_path, i), "expected extension of a supported image format"),
+            }
+        }
+        naming.extensions = extensions;
+    }
+
+    let suffixes_path = join(path, "suffixes");
+    if expect_object(&value["suffixes"], &suffixes_path, diag) {
+        for (map, entry) in value["suffixes"].entries() {
+            match entry.as_str() {
+                Some(suffix) => {
+                    naming.suffixes.insert(map.to_string(), suffix.to_string());
+                }
+                None => diag.error(&join(&suffixes_path, map), "expected string"),
+            }
+        }
+    }
+    return naming;
+}
+
+/// Parses a normal map layout name.
+fn parse_normal_layout(value: &json::JsonValue, base: NormalLayout, path: &str, diag: &mut Diagnostics) -> NormalLayout {
+    if value.is_null() {
+        return base;
+    }
+    return value.as_str().and_then(NormalLayout::parse).unwrap_or_else(|| {
+        diag.error(path, "expected one of \"xyz\", \"xy\", or \"dxt5nm\"");
+        base
+    });
+}
+
+/// Parses an object of named packing layouts into the given map.
+fn parse_layouts(value: &json::JsonValue, layouts: &mut HashMap<String, PackLayout>, path: &str, diag: &mut Diagnostics) {
+    if !expect_object(value, path, diag) {
+        return;
+    }
+    for (name, entry) in value.entries() {
+        match PackLayout::from_json(entry) {
+            Some(layout) => {
+                layouts.insert(name.to_string(), layout);
+            }
+            None => diag.error(&join(path, name), "malformed packing layout"),
+        }
+    }
+}
+
+/// Parses an object of packed source textures, mapping each source to a layout name or inline layout.
+/// Layout names are checked against the given layouts and presets.
+fn parse_unpack(
+    value: &json::JsonValue,
+    layouts: &HashMap<String, PackLayout>,
+    path: &str,
+    diag: &mut Diagnostics,
+) -> Vec<(String, UnpackRef)> {
+    let mut unpack = Vec::new();
+    if !expect_object(value, path, diag) {
+        return unpack;
+    }
+    for (source, entry) in value.entries() {
+        let entry_path = join(path, source);
+        match entry.as_str() {
+            Some(name) if layouts.contains_key(name) || op::pack::preset(name).is_some() => {
+                unpack.push((source.to_string(), UnpackRef::Named(name.to_string())));
+            }
+            Some(name) => diag.error(&entry_path, &format!("unknown packing layout '{0}'", name)),
+            None => match PackLayout::from_json(entry) {
+                Some(layout) => unpack.push((source.to_string(), UnpackRef::Inline(layout))),
+                None => diag.error(&entry_path, "malformed packing layout"),
+            },
+        }
+    }
+    return unpack;
+}
+
+/// Resolves packed source textures against the given layouts.
+fn resolve_unpack(unpack: &[(String, UnpackRef)], layouts: &HashMap<String, PackLayout>) -> Vec<(String, PackLayout)> {
+    return unpack
+        .iter()
+        .filter_map(|(source, layout)| {
+            let layout = match layout {
+                UnpackRef::Named(name) => layouts.get(name).cloned().or_else(|| op::pack::preset(name))?,
+                UnpackRef::Inline(layout) => layout.clone(),
+            };
+            Some((source.clone(), layout))
+        })
+        .collect();
+}
+
+/// Parses an object of color spaces by map name into the given map, where `true` is sRGB.
+fn parse_color_spaces(value: &json::JsonValue, color_spaces: &mut HashMap<String, bool>, path: &str, diag: &mut Diagnostics) {
+    if !expect_object(value, path, diag) {
+        return;
+    }
+    for (map, entry) in value.entries() {
+        match entry.as_str() {
+            Some("srgb") => {
+                color_spaces.insert(map.to_string(), true);
+            }
+            Some("linear") => {
+                color_spaces.insert(map.to_string(), false);
+            }
+            _ => diag.error(&join(path, map), "expected \"srgb\" or \"linear\""),
+        }
+    }
+}
+
+/// Parses an object of mip filters by map name into the given map.
+fn parse_mip_filters(value: &json::JsonValue, filters: &mut HashMap<String, MipFilter>, path: &str, diag: &mut Diagnostics) {
+    if !expect_object(value, path, diag) {
+        return;
+    }
+    for (map, entry) in value.entries() {
+        match entry.as_str().and_then(MipFilter::parse) {
+            Some(filter) => {
+                filters.insert(map.to_string(), filter);
+            }
+            None => diag.error(
+                &join(path, map),
+                "expected one of \"box\", \"kaiser\", \"normal\", \"max\", or \"min\"",
+            ),
+        }
+    }
+}
+
+/// Material settings that are inherited from the pipeline, `globals`, and templates, before being resolved into a material.
+#[derive(Clone)]
+struct Inherited {
+    max_dimension: Option<u32>,
+    /// Size of output textures relative to `res_base`, which replaces `max_dimension` if set.
+    scale: Option<f64>,
+    res_base: Option<u32>,
+    alpha: bool,
+    flip_normals: bool,
+    renormalize_normals: bool,
+    format: OutputFormat,
+    naming: Naming,
+    normal_source: NormalLayout,
+    layouts: HashMap<String, PackLayout>,
+    unpack: Vec<(String, UnpackRef)>,
+    color_spaces: HashMap<String, bool>,
+    mip_filters: HashMap<String, MipFilter>,
+    gpu_budget: Option<u64>,
+    budget_action: BudgetAction,
+    /// Names of the maps to output.
+    channels: Option<Vec<String>>,
+}
+
+/// Parses the material settings of an object, such as `globals`, a template, or a material, on top of the settings it inherits.
+/// Settings of the object replace inherited ones, while layouts, packed sources, color spaces, and mip filters are merged by name.
+fn parse_layer(value: &json::JsonValue, base: &Inherited, path: &str, diag: &mut Diagnostics) -> Inherited {
+    let mut layer = base.clone();
+    // Output size is either absolute or relative to the base resolution, whichever was declared last
+    if let Some(max_dimension) = parse_dimension(&value["max_dimension"], &join(path, "max_dimension"), diag) {
+        layer.max_dimension = Some(max_dimension);
+        layer.scale = None;
+    }
+    if let Some(scale) = parse_scale(&value["scale"], &join(path, "scale"), diag) {
+        if !value["max_dimension"].is_null() {
+            diag.error(&join(path, "scale"), "cannot be declared together with max_dimension");
+        }
+        layer.scale = Some(scale);
+    }
+    if let Some(res_base) = parse_dimension(&value["res_base"], &join(path, "res_base"), diag) {
+        layer.res_base = Some(res_base);
+    }
+    layer.alpha = parse_bool(&value["alpha"], &join(path, "alpha"), base.alpha, diag);
+    layer.flip_normals = parse_bool(&value["flip_normals"], &join(path, "flip_normals"), base.flip_normals, diag);
+    layer.renormalize_normals =
+        parse_bool(&value["renormalize_normals"], &join(path, "renormalize_normals"), base.renormalize_normals, diag);
+    layer.format = parse_format(&value["format"], base.format, &join(path, "format"), diag);
+    layer.naming = parse_naming(&value["naming"], &base.naming, &join(path, "naming"), diag);
+    layer.normal_source = parse_normal_layout(&value["normal_source"], base.normal_source, &join(path, "normal_source"), diag);
+    layer.gpu_budget = parse_bytes(&value["gpu_budget"], &join(path, "gpu_budget"), diag).or(base.gpu_budget);
+    layer.budget_action = parse_budget_action(&value["budget_action"], base.budget_action, &join(path, "budget_action"), diag);
+
+    // Layouts, packed sources, color spaces, and mip filters of this object take priority over inherited ones
+    parse_layouts(&value["layouts"], &mut layer.layouts, &join(path, "layouts"), diag);
+    layer.unpack = parse_unpack(&value["unpack"], &layer.layouts, &join(path, "unpack"), diag);
+    layer.unpack.extend(base.unpack.iter().cloned());
+    parse_color_spaces(&value["color_spaces"], &mut layer.color_spaces, &join(path, "color_spaces"), diag);
+    parse_mip_filters(&value["mip_filters"], &mut layer.mip_filters, &join(path, "mip_filters"), diag);
+
+    let channels_path = join(path, "channels");
+    if value["channels"].is_null() {
+        return layer;
+    }
+    if !value["channels"].is_array() {
+        diag.error(&channels_path, "expected array of map names");
+        return layer;
+    }
+    let mut channels: Vec<String> = Vec::new();
+    for (i, member) in value["channels"].members().enumerate() {
+        let Some(channel) = member.as_str() else {
+            diag.error(&format!("{0}[{1}]", channels_path, i), "expected string");
+            continue;
+        };
+        if channels.iter().any(|c| c == channel) {
+            diag.warn(&format!("{0}[{1}]", channels_path, i), &format!("duplicate channel '{0}', ignoring", channel));
+            continue;
+        }
+        channels.push(channel.to_string());
+    }
+    layer.channels = Some(channels);
+    return layer;
+}
+
+/// Templates and materials that have been inherited from, by JSON path, or `None` if they failed to parse.
+type Resolved = HashMap<String, Option<Inherited>>;
+
+/// Resolves the settings inherited by an object, from the template or material named by its `extends` key, or from `globals` if it has none.
+/// `chain` holds the JSON paths of the objects currently being resolved, to detect circular inheritance.
+fn parse_extends(
+    value: &json::JsonValue,
+    path: &str,
+    config: &json::JsonValue,
+    globals: &Inherited,
+    resolved: &mut Resolved,
+    chain: &mut Vec<String>,
+    diag: &mut Diagnostics,
+) -> Option<Inherited> {
+    let extends = &value["extends"];
+    if extends.is_null() {
+        return Some(globals.clone());
+    }
+    let extends_path = join(path, "extends");
+    let Some(name) = extends.as_str() else {
+        diag.error(&extends_path, "expected name of a template or material");
+        return None;
+    };
+
+    // Templates take priority over materials of the same name
+    let parent_path = match (config["templates"].has_key(name), config["materials"].has_key(name)) {
+        (true, _) => join("templates", name),
+        (false, true) => join("materials", name),
+        (false, false) => {
+            diag.error(&extends_path, &format!("unknown template or material '{0}'", name));
+            return None;
+        }
+    };
+    if chain.contains(&parent_path) {
+        chain.push(parent_path);
+        diag.error(&extends_path, &format!("circular inheritance, {0}", chain.join(" -> ")));
+        chain.pop();
+        return None;
+    }
+    return parse_inherited(&parent_path, config, globals, resolved, chain, diag);
+}
+
+/// Parses the settings of a template or material at the given JSON path, including those it inherits, for other objects to inherit.
+/// Each object is only parsed once.
+fn parse_inherited(
+    path: &str,
+    config: &json::JsonValue,
+    globals: &Inherited,
+    resolved: &mut Resolved,
+    chain: &mut Vec<String>,
+    diag: &mut Diagnostics,
+) -> Option<Inherited> {
+    if let Some(layer) = resolved.get(path) {
+        return layer.clone();
+    }
+    let (section, name) = path.split_once('.').unwrap();
+    let value = &config[section][name];
+    if !value.is_object() {
+        diag.error(path, "expected object");
+        resolved.insert(path.to_string(), None);
+        return None;
+    }
+    check_keys(value, &MATERIAL_KEYS, path, diag);
+
+    chain.push(path.to_string());
+    let base = parse_extends(value, path, config, globals, resolved, chain, diag);
+    chain.pop();
+    let layer = base.map(|base| parse_layer(value, &base, path, diag));
+    resolved.insert(path.to_string(), layer.clone());
+    return layer;
+}
+
+/// Parses a single material on top of the settings it inherits, and resolves its channels.
+fn parse_material(
+    name: &str,
+    mat: &json::JsonValue,
+    base: &Inherited,
+    diag: &mut Diagnostics,
+) -> Option<MaterialConfig> {
+    let path = join("materials", name);
+    if !mat.is_object() {
+        diag.error(&path, "expected object");
+        return None;
+    }
+    check_keys(mat, &MATERIAL_KEYS, &path, diag);
+    if mat["max_dimension"].is_null() && mat["scale"].is_null() && base.max_dimension.is_none() && base.scale.is_none() {
+        diag.error(&join(&path, "max_dimension"), "missing required integer");
+    }
+    let layer = parse_layer(mat, base, &path, diag);
+    let max_dimension = match (layer.scale, layer.res_base) {
+        (Some(scale), Some(res_base)) => Some(((res_base as f64 * scale).round() as u32).max(1)),
+        (Some(_), None) => {
+            diag.error(&join(&path, "scale"), "requires res_base to be declared");
+            None
+        }
+        (None, _) => layer.max_dimension,
+    };
+    if layer.channels.is_none() && mat["channels"].is_null() {
+        diag.error(&join(&path, "channels"), "expected array of map names");
+    }
+
+    let channels = layer
+        .channels?
+        .iter()
+        .map(|channel| ChannelConfig {
+            name: channel.clone(),
+            layout: layer.layouts.get(channel).cloned().or_else(|| op::pack::preset(channel)),
+            mip_filter: layer.mip_filters.get(channel).copied().unwrap_or_else(|| op::mip::default_filter(channel)),
+        })
+        .collect();
+
+    return Some(MaterialConfig {
+        name: name.to_string(),
+        max_dimension: max_dimension?,
+        res_base: layer.res_base,
+        alpha: layer.alpha,
+        flip_normals: layer.flip_normals,
+        renormalize_normals: layer.renormalize_normals,
+        format: layer.format,
+        naming: layer.naming,
+        normal_source: layer.normal_source,
+        unpack: resolve_unpack(&layer.unpack, &layer.layouts),
+        color_spaces: layer.color_spaces,
+        gpu_budget: layer.gpu_budget,
+        budget_action: layer.budget_action,
+        channels,
+    });
+}
+
+/// Parses a single profile, applying its overrides to a copy of every material.
+/// The profile's `max_dimension` limits every material, while its `format` settings take priority over those of materials.
+/// Per-material overrides take priority over both.
+fn parse_profile(
+    name: &str,
+    profile: &json::JsonValue,
+    output: &str,
+    materials: &[MaterialConfig],
+    diag: &mut Diagnostics,
+) -> Option<ProfileConfig> {
+    let path = join("profiles", name);
+    if !profile.is_object() {
+        diag.error(&path, "expected object");
+        return None;
+    }
+    check_keys(profile, &PROFILE_KEYS, &path, diag);
+
+    // Profiles output into a folder of their own by default
+    let output = match profile["output"].is_null() {
+        true => Path::new(output).join(name).to_str().unwrap().to_string(),
+        false => parse_string(&profile["output"], &join(&path, "output"), diag)?,
+    };
+    let max_dimension = parse_dimension(&profile["max_dimension"], &join(&path, "max_dimension"), diag);
+    // Formats are applied over the format of each material, so only the declared settings change
+    let format_path = join(&path, "format");
+    let format = match &profile["format"] {
+        value if value.is_null() => None,
+        value => {
+            if value.is_object() {
+                check_keys(value, &FORMAT_KEYS, &format_path, diag);
+            }
+            let valid = OutputFormat::from_json(value, OutputFormat::default()).is_some();
+            if !valid {
+                diag.error(&format_path, "malformed output format");
+            }
+            valid.then_some(value)
+        }
+    };
+
+    let overrides_path = join(&path, "materials");
+    let overrides = &profile["materials"];
+    if expect_object(overrides, &overrides_path, diag) {
+        for (mat_name, entry) in overrides.entries() {
+            let entry_path = join(&overrides_path, mat_name);
+            if !materials.iter().any(|mat| mat.name == mat_name) {
+                diag.error(&entry_path, &format!("unknown material '{0}'", mat_name));
+            } else if !entry.is_object() {
+                diag.error(&entry_path, "expected object");
+            } else {
+                check_keys(entry, &PROFILE_MATERIAL_KEYS, &entry_path, diag);
+            }
+        }
+    }
+
+    let materials = materials
+        .iter()
+        .map(|mat| {
+            let mut mat = mat.clone();
+            if let Some(max_dimension) = max_dimension {
+                mat.max_dimension = mat.max_dimension.min(max_dimension);
+            }
+            if let Some(format) = format {
+                mat.format = OutputFormat::from_json(format, mat.format).expect("Profile format was validated");
+            }
+
+            let entry = &overrides[mat.name.as_str()];
+            let entry_path = join(&overrides_path, &mat.name);
+            if entry.is_object() {
+                let dimension = parse_dimension(&entry["max_dimension"], &join(&entry_path, "max_dimension"), diag);
+                mat.max_dimension = dimension.unwrap_or(mat.max_dimension);
+                mat.format = parse_format(&entry["format"], mat.format, &join(&entry_path, "format"), diag);
+            }
+            mat
+        })
+        .collect();
+
+    return Some(ProfileConfig {
+        name: name.to_string(),
+        output,
+        materials,
+    });
+}
+
+/// Parses and validates the contents of a pipeline file.
+/// Every problem found is recorded in `diag`. Returns `None` if there were any errors.
+/// `dir` is the directory of the pipeline file, which is needed to discover materials.
+pub fn parse(contents: &str, dir: Option<&Path>, diag: &mut Diagnostics) -> Option<PipelineConfig> {
+    let config = match json::parse(contents) {
+        Ok(config) => config,
+        Err(err) => {
+            diag.error("(root)", &format!("invalid JSON, {0}", err));
+            return None;
+        }
+    };
+    if !config.is_object() {
+        diag.error("(root)", "expected object");
+        return None;
+    }
+    check_keys(&config, &PIPELINE_KEYS, "", diag);
+
+    let input = parse_string(&config["input"], "input", diag);
+    let output = parse_string(&config["output"], "output", diag);
+    let gpu_budget = parse_bytes(&config["gpu_budget"], "gpu_budget", diag);
+    let budget_action = parse_budget_action(&config["budget_action"], BudgetAction::Warn, "budget_action", diag);
+
+    let mut layouts: HashMap<String, PackLayout> = HashMap::new();
+    parse_layouts(&config["layouts"], &mut layouts, "layouts", diag);
+    let mut color_spaces: HashMap<String, bool> = HashMap::new();
+    parse_color_spaces(&config["color_spaces"], &mut color_spaces, "color_spaces", diag);
+    let mut mip_filters: HashMap<String, MipFilter> = HashMap::new();
+    parse_mip_filters(&config["mip_filters"], &mut mip_filters, "mip_filters", diag);
+
+    let base = Inherited {
+        max_dimension: None,
+        scale: None,
+        res_base: None,
+        alpha: false,
+        flip_normals: parse_bool(&config["flip_normals"], "flip_normals", false, diag),
+        renormalize_normals: parse_bool(&config["renormalize_normals"], "renormalize_normals", false, diag),
+        format: parse_format(&config["format"], OutputFormat::default(), "format", diag),
+        naming: parse_naming(&config["naming"], &Naming::default(), "naming", diag),
+        normal_source: parse_normal_layout(&config["normal_source"], NormalLayout::Xyz, "normal_source", diag),
+        unpack: parse_unpack(&config["unpack"], &layouts, "unpack", diag),
+        layouts,
+        color_spaces,
+        mip_filters,
+        gpu_budget: None,
+        budget_action,
+        channels: None,
+    };
+
+    // Defaults for every material
+    let globals = match expect_object(&config["globals"], "globals", diag) {
+        true => {
+            check_keys(&config["globals"], &GLOBALS_KEYS, "globals", diag);
+            parse_layer(&config["globals"], &base, "globals", diag)
+        }
+        false => base,
+    };
+
+    let mut resolved: Resolved = HashMap::new();
+    if expect_object(&config["templates"], "templates", diag) {
+        for (name, _) in config["templates"].entries() {
+            parse_inherited(&join("templates", name), &config, &globals, &mut resolved, &mut Vec::new(), diag);
+        }
+    }
+
+    // Materials may be left out entirely if they're discovered
+    let discover = parse_bool(&config["discover"], "discover", false, diag);
+    let mut materials: Vec<MaterialConfig> = Vec::new();
+    let discovered_only = discover && config["materials"].is_null();
+    if !config["materials"].is_object() && !discovered_only {
+        diag.error("materials", "expected object of materials");
+    }
+    for (name, mat) in config["materials"].entries() {
+        let path = join("materials", name);
+        let mut chain = vec![path.clone()];
+        let base = parse_extends(mat, &path, &config, &globals, &mut resolved, &mut chain, diag);
+        if let Some(material) = base.and_then(|base| parse_material(name, mat, &base, diag)) {
+            materials.push(material);
+        }
+    }
+
+    // Materials discovered in the input directory use the default settings, unless they're declared
+    if let (true, Some(dir), Some(input)) = (discover, dir, input.as_ref()) {
+        if globals.max_dimension.is_none() && globals.scale.is_none() {
+            diag.error("discover", "discovered materials need a max_dimension or scale in globals");
+        } else {
+            for (name, maps) in discover::scan(&dir.join(input), &globals.naming) {
+                if config["materials"].has_key(&name) {
+                    continue;
+                }
+                if let Some(material) = parse_material(&name, &json::object! { channels: maps }, &globals, diag) {
+                    materials.push(material);
+                }
+            }
+        }
+    }
+
+    let mut profiles: Vec<ProfileConfig> = Vec::new();
+    if expect_object(&config["profiles"], "profiles", diag) {
+        for (name, profile) in config["profiles"].entries() {
+            let output = output.as_deref().unwrap_or_default();
+            if let Some(profile) = parse_profile(name, profile, output, &materials, diag) {
+                profiles.push(profile);
+            }
+        }
+    }
+
+    if !diag.errors.is_empty() {
+        return None;
+    }
+    return Some(PipelineConfig {
+        input: input?,
+        output: output?,
+        gpu_budget,
+        budget_action,
+        materials,
+        profiles,
+    });
+}
+
+/// Loads, parses, and validates a pipeline file.
+/// Every problem found is recorded in `diag`. Returns `None` if there were any errors.
+pub fn load(config_file: &Path, diag: &mut Diagnostics) -> Option<PipelineConfig> {
+    return match read_to_string(config_file) {
+        Ok(contents) => parse(&contents, config_file.parent(), diag),
+        Err(err) => {
+            diag.error("(root)", &format!("failed to read {0}, {1}", config_file.display(), err));
+            None
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Diagnostics};
+    use crate::format::output::{Container, Encoding};
+    use crate::op::mip::MipFilter;
+    use std::path::Path;
+
+    #[test]
+    fn parse_config() {
+        let mut diag = Diagnostics::default();
+        let config = parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "format": "ktx2",
+                "globals": {},
+                "mip_filters": { "mask": "max" },
+                "materials": {
+                    "mat_a": { "max_dimension": 512, "alpha": true, "channels": ["basecolor", "arm", "mask", "ao"] },
+                    "mat_b": { "max_dimension": 64, "format": "dds", "colour": "red", "channels": [] }
+                }
+            }"#,
+            None,
+            &mut diag,
+        )
+        .expect("Valid config");
+        assert!(diag.errors.is_empty());
+        assert_eq!(diag.warnings, vec!["materials.mat_b.colour: unknown key, ignoring"]);
+
+        assert_eq!(config.materials.len(), 2);
+        let mat_a = &config.materials[0];
+        assert_eq!((mat_a.name.as_str(), mat_a.max_dimension, mat_a.alpha), ("mat_a", 512, true));
+        assert_eq!(mat_a.format.container, Container::Ktx2);
+        assert!(mat_a.channels[0].layout.is_none());
+        assert!(mat_a.channels[1].layout.is_some());
+        assert_eq!(mat_a.channels[2].mip_filter, MipFilter::Max);
+        assert_eq!(mat_a.source_maps(), vec!["basecolor", "arm", "ao", "roughness", "metallic", "mask"]);
+        assert_eq!(config.materials[1].format.container, Container::Dds);
+    }
+
+    #[test]
+    fn report_every_error() {
+        let mut diag = Diagnostics::default();
+        let config = parse(
+            r#"{
+                "input": 5,
+                "flip_normals": "yes",
+                "unpack": { "orm": "missing" },
+                "materials": {
+                    "mat_body": { "max_dimension": "big", "channels": ["basecolor", 7] },
+                    "mat_eye": { "channels": "basecolor" }
+                }
+            }"#,
+            None,
+            &mut diag,
+        );
+        assert!(config.is_none());
+        assert_eq!(
+            diag.errors,
+            vec![
+                "input: expected string",
+                "output: missing required string",
+                "flip_normals: expected boolean",
+                "unpack.orm: unknown packing layout 'missing'",
+                "materials.mat_body.max_dimension: expected integer",
+                "materials.mat_body.channels[1]: expected string",
+                "materials.mat_eye.max_dimension: missing required integer",
+                "materials.mat_eye.channels: expected array of map names",
+            ]
+        );
+
+        let mut diag = Diagnostics::default();
+        assert!(parse("{ \"input\": ", None, &mut diag).is_none());
+        assert!(diag.errors[0].starts_with("(root): invalid JSON"));
+    }
+
+    #[test]
+    fn apply_profiles() {
+        let mut diag = Diagnostics::default();
+        let config = parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "format": "ktx2",
+                "profiles": {
+                    "pc": { "output": "./pc/" },
+                    "mobile": {
+                        "max_dimension": 256,
+                        "format": { "encoding": "bcn" },
+                        "materials": { "mat_b": { "max_dimension": 64, "format": "png" } }
+                    }
+                },
+                "materials": {
+                    "mat_a": { "max_dimension": 1024, "channels": ["basecolor"] },
+                    "mat_b": { "max_dimension": 128, "channels": ["basecolor"] }
+                }
+            }"#,
+            None,
+            &mut diag,
+        )
+        .expect("Valid config");
+        assert!(diag.warnings.is_empty());
+
+        let pc = config.with_profile("pc").expect("PC profile");
+        assert_eq!(pc.output, "./pc/");
+        assert_eq!(pc.materials[0].max_dimension, 1024);
+
+        let mobile = config.with_profile("mobile").expect("Mobile profile");
+        assert_eq!(mobile.output, "./out/mobile");
+        assert_eq!((mobile.materials[0].max_dimension, mobile.materials[1].max_dimension), (256, 64));
+        assert_eq!(mobile.materials[0].format.container, Container::Ktx2);
+        assert_eq!(mobile.materials[0].format.encoding(), Encoding::Bcn);
+        assert_eq!(mobile.materials[1].format.container, Container::Png);
+        assert!(config.with_profile("console").is_none());
+
+        let mut diag = Diagnostics::default();
+        parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "profiles": { "mobile": { "format": "gif", "materials": { "mat_c": {} } } },
+                "materials": {}
+            }"#,
+            None,
+            &mut diag,
+        );
+        assert_eq!(
+            diag.errors,
+            vec![
+                "profiles.mobile.format: malformed output format",
+                "profiles.mobile.materials.mat_c: unknown material 'mat_c'",
+            ]
+        );
+    }
+
+    #[test]
+    fn inherit_settings() {
+        let mut diag = Diagnostics::default();
+        let config = parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "format": "ktx2",
+                "globals": {
+                    "max_dimension": 1024,
+                    "flip_normals": true,
+                    "channels": ["basecolor", "normal"],
+                    "naming": { "suffixes": { "basecolor": "D", "normal": "N" } },
+                    "baking_samples": 64
+                },
+                "templates": {
+                    "character": { "alpha": true, "mip_filters": { "mask": "max" }, "channels": ["basecolor", "normal", "mask"] },
+                    "hero": {
+                        "extends": "character",
+                        "max_dimension": 4096,
+                        "format": { "encoding": "bcn" },
+                        "naming": { "input": ["{material}_{map}", "Body_{map}"], "output": "T_{material}_{suffix}" }
+                    }
+                },
+                "materials": {
+                    "mat_rock": {},
+                    "mat_body": { "extends": "hero", "flip_normals": false },
+                    "mat_eye": { "extends": "mat_body", "max_dimension": 256 }
+                }
+            }"#,
+            None,
+            &mut diag,
+        )
+        .expect("Valid config");
+        assert!(diag.errors.is_empty());
+        assert_eq!(diag.warnings, vec!["globals.baking_samples: unknown key, ignoring"]);
+
+        let rock = &config.materials[0];
+        assert_eq!((rock.max_dimension, rock.alpha, rock.flip_normals), (1024, false, true));
+        assert_eq!(rock.channels.len(), 2);
+
+        let body = &config.materials[1];
+        assert_eq!((body.max_dimension, body.alpha, body.flip_normals), (4096, true, false));
+        assert_eq!((body.format.container, body.format.encoding()), (Container::Ktx2, Encoding::Bcn));
+        assert_eq!(body.channels[2].mip_filter, MipFilter::Max);
+        assert_eq!(body.naming.inputs.len(), 2);
+        assert_eq!(body.naming.output_path(Path::new("out"), "mat_body", "normal", "ktx2"), Path::new("out/T_mat_body_N.ktx2"));
+        assert_eq!(rock.naming.output_path(Path::new("out"), "mat_rock", "basecolor", "ktx2"), Path::new("out/mat_rock_basecolor.ktx2"));
+
+        let eye = &config.materials[2];
+        assert_eq!((eye.max_dimension, eye.alpha, eye.flip_normals, eye.channels.len()), (256, true, false, 3));
+
+        let mut diag = Diagnostics::default();
+        let config = parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "templates": { "a": { "extends": "b" }, "b": { "extends": "a" } },
+                "materials": {
+                    "mat_a": { "extends": "a", "max_dimension": 64, "channels": [] },
+                    "mat_b": { "extends": "missing", "max_dimension": 64, "channels": [] },
+                    "mat_c": { "extends": "mat_c", "max_dimension": 64, "channels": [] }
+                }
+            }"#,
+            None,
+            &mut diag,
+        );
+        assert!(config.is_none());
+        assert_eq!(
+            diag.errors,
+            vec![
+                "templates.b.extends: circular inheritance, templates.a -> templates.b -> templates.a",
+                "materials.mat_b.extends: unknown template or material 'missing'",
+                "materials.mat_c.extends: circular inheritance, materials.mat_c -> materials.mat_c",
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_size() {
+        let mut diag = Diagnostics::default();
+        let config = parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "globals": { "scale": 0.25, "channels": ["basecolor"] },
+                "materials": {
+                    "mat_a": { "res_base": 4096 },
+                    "mat_b": { "res_base": 2048, "max_dimension": 1024 },
+                    "mat_c": { "res_base": 100, "scale": 0.5 }
+                }
+            }"#,
+            None,
+            &mut diag,
+        )
+        .expect("Valid config");
+        let dimensions: Vec<u32> = config.materials.iter().map(|mat| mat.max_dimension).collect();
+        assert_eq!(dimensions, vec![1024, 1024, 50]);
+        assert_eq!(config.materials[0].res_base, Some(4096));
+
+        let mut diag = Diagnostics::default();
+        let config = parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "materials": {
+                    "mat_a": { "scale": 0.5, "channels": [] },
+                    "mat_b": { "res_base": 512, "scale": 2, "channels": [] },
+                    "mat_c": { "res_base": 512, "max_dimension": 64, "scale": 0.5, "channels": [] }
+                }
+            }"#,
+            None,
+            &mut diag,
+        );
+        assert!(config.is_none());
+        assert_eq!(
+            diag.errors,
+            vec![
+                "materials.mat_a.scale: requires res_base to be declared",
+                "materials.mat_b.scale: expected number greater than 0 and at most 1",
+                "materials.mat_c.scale: cannot be declared together with max_dimension",
+            ]
+        );
+    }
+}
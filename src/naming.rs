@@ -0,0 +1,283 @@
+use crate::util;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Alternative names that source maps are commonly exported under, tried after the name of the map itself.
+const ALIASES: [(&str, &[&str]); 6] = [
+    ("basecolor", &["albedo", "diffuse", "base_color", "color"]),
+    ("normal", &["nrm", "normals", "norm"]),
+    ("roughness", &["rough"]),
+    ("metallic", &["metal", "metalness"]),
+    ("ao", &["occlusion", "ambient_occlusion"]),
+    ("height", &["displacement", "disp"]),
+];
+
+/// Extensions of source textures, in the order they are tried.
+const EXTENSIONS: [&str; 9] = ["png", "tga", "tif", "tiff", "exr", "jpg", "jpeg", "webp", "bmp"];
+
+/// Placeholders allowed in source file name templates.
+pub const INPUT_PLACEHOLDERS: [&str; 2] = ["material", "map"];
+
+/// Placeholders allowed in output file name templates.
+pub const OUTPUT_PLACEHOLDERS: [&str; 3] = ["material", "map", "suffix"];
+
+/// File name conventions of the source and output textures of a material.
+/// Templates are file names without an extension, where `{material}` and `{map}` are replaced with the names of the material and map.
+/// Source file names, including their aliases and extensions, are matched case-insensitively.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Naming {
+    /// Templates of source file names, tried in order.
+    pub inputs: Vec<String>,
+    /// Template of output file names, which may also use `{suffix}`.
+    pub output: String,
+    /// Alternative source names by map name, replacing the built-in aliases of the map.
+    pub aliases: HashMap<String, Vec<String>>,
+    /// Suffixes of output file names by map name, which default to the name of the map.
+    pub suffixes: HashMap<String, String>,
+    /// Extensions of source textures, tried in order for each file name.
+    pub extensions: Vec<String>,
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        return Naming {
+            inputs: vec!["{material}_{map}".to_string()],
+            output: "{material}_{map}".to_string(),
+            aliases: HashMap::new(),
+            suffixes: HashMap::new(),
+            extensions: EXTENSIONS.iter().map(|extension| extension.to_string()).collect(),
+        };
+    }
+}
+
+/// Returns the placeholders of a template that are not in the list of known placeholders.
+pub fn unknown_placeholders<'a>(template: &'a str, known: &[&str]) -> Vec<&'a str> {
+    return template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+        .filter(|name| !known.contains(name))
+        .collect();
+}
+
+/// Listing of the files in a directory of source textures, read once so that looking up sources doesn't read the directory again.
+/// Files added or removed after the listing was read are not seen.
+#[derive(Clone, Debug, Default)]
+pub struct SourceDir {
+    path: PathBuf,
+    /// Names of the files in the directory, by their lowercase name.
+    files: HashMap<String, Vec<String>>,
+}
+
+impl SourceDir {
+    /// Reads the listing of a directory. Directories that can't be read are treated as empty.
+    pub fn read(path: &Path) -> SourceDir {
+        let mut files: HashMap<String, Vec<String>> = HashMap::new();
+        if let Ok(entries) = fs::read_dir(path) {
+            for name in entries.flatten().filter_map(|entry| entry.file_name().into_string().ok()) {
+                files.entry(name.to_ascii_lowercase()).or_default().push(name);
+            }
+        }
+        for names in files.values_mut() {
+            names.sort();
+        }
+        return SourceDir { path: path.to_path_buf(), files };
+    }
+
+    /// Returns the path of the directory.
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    /// Returns the existing files that match any of the given file names case-insensitively, in the order of the names.
+    fn existing(&self, names: &[String]) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for name in names {
+            for file in self.files.get(&name.to_ascii_lowercase()).into_iter().flatten() {
+                let path = self.path.join(file);
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        return paths;
+    }
+}
+
+/// Fills in the placeholders of a template.
+fn fill(template: &str, material: &str, map: &str, suffix: &str) -> String {
+    return template.replace("{material}", material).replace("{map}", map).replace("{suffix}", suffix);
+}
+
+impl Naming {
+    /// Returns the names a source map may be found under: the name of the map, then its aliases.
+    fn source_names<'a>(&'a self, map: &'a str) -> Vec<&'a str> {
+        let mut names = vec![map];
+        match self.aliases.get(map) {
+            Some(aliases) => names.extend(aliases.iter().map(|alias| alias.as_str())),
+            None => {
+                let builtin = ALIASES.iter().find(|(name, _)| *name == map);
+                names.extend(builtin.iter().flat_map(|(_, aliases)| aliases.iter().copied()));
+            }
+        }
+        return names;
+    }
+
+    /// Returns every map name and alias with built-in or declared aliases, which source names are recognised by.
+    pub fn known_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for (map, aliases) in ALIASES.iter() {
+            names.push(map);
+            names.extend(aliases.iter().copied());
+        }
+        for (map, aliases) in self.aliases.iter() {
+            names.push(map);
+            names.extend(aliases.iter().map(|alias| alias.as_str()));
+        }
+        return names;
+    }
+
+    /// Returns the name of the map that a source name stands for, resolving aliases. Unknown names are returned as-is.
+    /// Names are compared case-insensitively, so `BaseColor` is `basecolor`.
+    /// Maps with a known color format, such as `diffuse`, stand for themselves rather than a built-in alias.
+    pub fn canonical_map(&self, name: &str) -> String {
+        let matches = |other: &str| other.eq_ignore_ascii_case(name);
+        if let Some(map) = self.aliases.keys().find(|map| matches(map)) {
+            return map.clone();
+        }
+        if let Some((map, _)) = ALIASES.iter().find(|(map, _)| matches(map)) {
+            return map.to_string();
+        }
+        let declared = self.aliases.iter().find(|(_, aliases)| aliases.iter().any(|alias| matches(alias)));
+        if let Some((map, _)) = declared {
+            return map.clone();
+        }
+        if util::map_is_known(&name.to_ascii_lowercase()) {
+            return name.to_ascii_lowercase();
+        }
+        let builtin = ALIASES
+            .iter()
+            .filter(|(map, _)| !self.aliases.contains_key(*map))
+            .find(|(_, aliases)| aliases.iter().any(|alias| matches(alias)));
+        return builtin.map_or(name, |(map, _)| map).to_string();
+    }
+
+    /// Returns every file name a source map of a material may have, in the order they are tried.
+    fn source_names_with_extensions(&self, material: &str, map: &str) -> Vec<String> {
+        return self.file_names(material, &self.source_names(map));
+    }
+
+    /// Returns every file name a material may have under the given map names, in the order they are tried.
+    fn file_names(&self, material: &str, names: &[&str]) -> Vec<String> {
+        let mut candidates: Vec<String> = Vec::new();
+        for template in self.inputs.iter() {
+            for name in names.iter() {
+                let file_name = fill(template, material, name, name);
+                for extension in self.extensions.iter() {
+                    let candidate = format!("{0}.{1}", file_name, extension);
+                    if !candidates.iter().any(|other| other.eq_ignore_ascii_case(&candidate)) {
+                        candidates.push(candidate);
+                    }
+                }
+            }
+        }
+        return candidates;
+    }
+
+    /// Returns true if the given file could be a source texture for a map of a material, ignoring case.
+    pub fn is_source(&self, file: &Path, material: &str, map: &str) -> bool {
+        let Some(file_name) = file.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        return self
+            .source_names_with_extensions(material, map)
+            .iter()
+            .any(|candidate| candidate.eq_ignore_ascii_case(file_name));
+    }
+
+    /// Returns the path of every existing source texture named after a map of a material itself, rather than one of its aliases.
+    pub fn own_sources(&self, directory: &SourceDir, material: &str, map: &str) -> Vec<PathBuf> {
+        return directory.existing(&self.file_names(material, &[map]));
+    }
+
+    /// Returns the path of every existing source texture for a map of a material, in the order they are tried.
+    /// Only the first is used, so there should be at most one.
+    pub fn sources(&self, directory: &SourceDir, material: &str, map: &str) -> Vec<PathBuf> {
+        return directory.existing(&self.source_names_with_extensions(material, map));
+    }
+
+    /// Returns the path of an existing source texture for a map of a material, if there is one.
+    pub fn source(&self, directory: &SourceDir, material: &str, map: &str) -> Option<PathBuf> {
+        return self.sources(directory, material, map).into_iter().next();
+    }
+
+    /// Returns the path of the source texture for a map of a material, or where it is expected to be if there is none.
+    pub fn source_path(&self, directory: &SourceDir, material: &str, map: &str) -> PathBuf {
+        return self.source(directory, material, map).unwrap_or_else(|| {
+            let candidates = self.source_names_with_extensions(material, map);
+            directory.path().join(&candidates[0])
+        });
+    }
+
+    /// Returns the path of the output texture for a map of a material, with the given file extension.
+    pub fn output_path(&self, directory: &Path, material: &str, map: &str, extension: &str) -> PathBuf {
+        let suffix = self.suffixes.get(map).map_or(map, |suffix| suffix.as_str());
+        return directory.join(format!("{0}.{1}", fill(&self.output, material, map, suffix), extension));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unknown_placeholders, Naming, SourceDir, INPUT_PLACEHOLDERS};
+    use crate::util::test_dir;
+    use std::fs;
+
+    #[test]
+    fn resolve_names() {
+        let files = ["Body_Albedo.png", "Body_BaseColor.png", "body.nrm.png", "mat_a_nrm.png", "mat_a_ao.tga", "mat_a_ao.EXR", "mat_a_Mask.PNG"];
+        let dir = test_dir("naming", &files);
+        let sources = SourceDir::read(&dir);
+
+        let default = Naming::default();
+        assert_eq!(default.source(&sources, "mat_a", "normal"), Some(dir.join("mat_a_nrm.png")));
+        assert_eq!(default.source_path(&sources, "mat_a", "roughness"), dir.join("mat_a_roughness.png"));
+        assert_eq!(default.sources(&sources, "mat_a", "ao"), vec![dir.join("mat_a_ao.tga"), dir.join("mat_a_ao.EXR")]);
+        let exr = Naming { extensions: vec!["exr".to_string()], ..Naming::default() };
+        assert_eq!(exr.source(&sources, "mat_a", "ao"), Some(dir.join("mat_a_ao.EXR")));
+        assert_eq!(default.source(&sources, "mat_a", "mask"), Some(dir.join("mat_a_Mask.PNG")));
+        assert!(default.is_source(&dir.join("MAT_A_NRM.png"), "mat_a", "normal"));
+        let body = Naming { inputs: vec!["Body_{map}".to_string()], ..Naming::default() };
+        assert_eq!(body.source(&sources, "body", "basecolor"), Some(dir.join("Body_BaseColor.png")));
+        assert_eq!(default.output_path(&dir, "mat_a", "normal", "ktx2"), dir.join("mat_a_normal.ktx2"));
+
+        // Listings are only read once
+        fs::write(dir.join("mat_a_roughness.png"), []).expect("Create test source file");
+        assert_eq!(default.source(&sources, "mat_a", "roughness"), None);
+        assert!(default.source(&SourceDir::read(&dir), "mat_a", "roughness").is_some());
+
+        let mut naming = Naming {
+            inputs: vec!["Body_{map}".to_string(), "body.{map}".to_string()],
+            output: "T_{material}_{suffix}".to_string(),
+            ..Naming::default()
+        };
+        naming.aliases.insert("basecolor".to_string(), vec!["BaseColor".to_string(), "Albedo".to_string()]);
+        naming.suffixes.insert("basecolor".to_string(), "D".to_string());
+        assert_eq!(naming.source(&sources, "body", "basecolor"), Some(dir.join("Body_BaseColor.png")));
+        assert_eq!(naming.source(&sources, "body", "normal"), Some(dir.join("body.nrm.png")));
+        assert_eq!(naming.output_path(&dir, "body", "basecolor", "dds"), dir.join("T_body_D.dds"));
+        assert_eq!(naming.output_path(&dir, "body", "orm", "dds"), dir.join("T_body_orm.dds"));
+
+        assert_eq!(naming.canonical_map("BaseColor"), "basecolor");
+        assert_eq!(naming.canonical_map("nrm"), "normal");
+        assert_eq!(naming.canonical_map("albedo"), "basecolor");
+        assert_eq!(default.canonical_map("Diffuse"), "diffuse");
+        assert_eq!(default.canonical_map("color"), "basecolor");
+        assert_eq!(default.canonical_map("occlusion"), "occlusion");
+        assert_eq!(naming.canonical_map("diffuse"), "diffuse");
+        assert_eq!(naming.canonical_map("Mask"), "mask");
+        assert_eq!(naming.canonical_map("emissive"), "emissive");
+
+        assert_eq!(unknown_placeholders("{material}_{map}_{size}", &INPUT_PLACEHOLDERS), vec!["size"]);
+    }
+}
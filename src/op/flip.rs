@@ -6,7 +6,7 @@ pub fn flip_green(texture: Rgb16Image) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
     let (_width, _height) = texture.dimensions();
 
     // Prep new image
-    let mut imgbuf = image::ImageBuffer::from(texture);
+    let mut imgbuf = texture;
     // Fill out pixels of new image with data from inputs
     for (_x, _y, pixel) in imgbuf.enumerate_pixels_mut() {
         *pixel = Rgb([pixel[0], u16::MAX - pixel[1], pixel[2]]);
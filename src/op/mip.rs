@@ -0,0 +1,228 @@
+use crate::util;
+use image::{DynamicImage, GenericImageView, Rgba, Rgba32FImage};
+use std::f32::consts::PI;
+
+/// Filters for downsampling one mip level into the next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MipFilter {
+    /// Averages each 2x2 block of texels.
+    Box,
+    /// Kaiser-windowed sinc, which keeps smaller mips sharper than a box filter.
+    Kaiser,
+    /// Averages each 2x2 block of normal vectors, and renormalizes the result.
+    Normal,
+    /// Takes the maximum of each 2x2 block, per channel. Keeps thin mask features from disappearing.
+    Max,
+    /// Takes the minimum of each 2x2 block, per channel.
+    Min,
+}
+
+impl MipFilter {
+    /// Parses a mip filter name.
+    pub fn parse(name: &str) -> Option<MipFilter> {
+        return match name {
+            "box" => Some(MipFilter::Box),
+            "kaiser" => Some(MipFilter::Kaiser),
+            "normal" => Some(MipFilter::Normal),
+            "max" => Some(MipFilter::Max),
+            "min" => Some(MipFilter::Min),
+            _ => None,
+        };
+    }
+}
+
+/// Returns the default mip filter for the given map name.
+pub fn default_filter(map_name: &str) -> MipFilter {
+    return match map_name {
+        "normal" => MipFilter::Normal,
+        _ => MipFilter::Box,
+    };
+}
+
+/// Returns the number of mip levels in a full mip chain for the given dimensions, including the base level.
+pub fn level_count(width: u32, height: u32) -> u32 {
+    return 32 - width.max(height).max(1).leading_zeros();
+}
+
+/// Generates a full mip chain for an image, from the base level down to 1x1.
+/// Each level is half the size of the previous one, rounded down (to a minimum of 1), and keeps the color format of the base level.
+/// If `srgb` is set, color is filtered in linear light.
+pub fn mip_chain(img: DynamicImage, filter: MipFilter, srgb: bool) -> Vec<DynamicImage> {
+    let (width, height) = img.dimensions();
+    let count = level_count(width, height);
+    let ct = img.color();
+
+    // Filter in linear floating point, so that each level is computed from the full precision of the last
+    let mut current = img.to_rgba32f();
+    if srgb {
+        util::image_to_linear(&mut current);
+    }
+
+    let mut levels: Vec<DynamicImage> = vec![img];
+    for _ in 1..count {
+        current = downsample(&current, filter);
+
+        let mut level = current.clone();
+        if srgb {
+            util::image_to_srgb(&mut level);
+        }
+        levels.push(util::convert_color(DynamicImage::from(level), ct));
+    }
+
+    return levels;
+}
+
+/// Downsamples an image to half its size with the given filter.
+fn downsample(img: &Rgba32FImage, filter: MipFilter) -> Rgba32FImage {
+    let (width, height) = img.dimensions();
+    let (out_w, out_h) = ((width / 2).max(1), (height / 2).max(1));
+
+    if filter == MipFilter::Kaiser {
+        return kaiser_downsample(img, out_w, out_h);
+    }
+
+    return Rgba32FImage::from_fn(out_w, out_h, |x, y| {
+        // Gather the 2x2 footprint, clamped to the image for odd or 1-texel dimensions
+        let xs = [(x * 2).min(width - 1), (x * 2 + 1).min(width - 1)];
+        let ys = [(y * 2).min(height - 1), (y * 2 + 1).min(height - 1)];
+        let texels = [
+            img.get_pixel(xs[0], ys[0]).0,
+            img.get_pixel(xs[1], ys[0]).0,
+            img.get_pixel(xs[0], ys[1]).0,
+            img.get_pixel(xs[1], ys[1]).0,
+        ];
+
+        let mut out = [0.0f32; 4];
+        for c in 0..4 {
+            let values = texels.map(|t| t[c]);
+            out[c] = match filter {
+                MipFilter::Max => values.into_iter().fold(f32::MIN, f32::max),
+                MipFilter::Min => values.into_iter().fold(f32::MAX, f32::min),
+                _ => values.iter().sum::<f32>() / 4.0,
+            };
+        }
+
+        if filter == MipFilter::Normal {
+            // Renormalize the averaged vector
+            let v = [0, 1, 2].map(|c| out[c] * 2.0 - 1.0);
+            let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+            if len > 1e-6 {
+                for c in 0..3 {
+                    out[c] = (v[c] / len) * 0.5 + 0.5;
+                }
+            }
+        }
+
+        Rgba(out)
+    });
+}
+
+/// Zeroth order modified Bessel function of the first kind, for the Kaiser window.
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..16 {
+        term *= (x / (2.0 * k as f32)).powi(2);
+        sum += term;
+    }
+    return sum;
+}
+
+/// Weight of the Kaiser-windowed sinc filter at distance `t`, in destination texels.
+fn kaiser_weight(t: f32) -> f32 {
+    const WIDTH: f32 = 1.5;
+    const ALPHA: f32 = 4.0;
+    if t.abs() >= WIDTH {
+        return 0.0;
+    }
+    let sinc = if t == 0.0 { 1.0 } else { (PI * t).sin() / (PI * t) };
+    let window = bessel_i0(ALPHA * (1.0 - (t / WIDTH).powi(2)).sqrt()) / bessel_i0(ALPHA);
+    return sinc * window;
+}
+
+/// Downsamples an image to the given size with a separable Kaiser-windowed sinc filter.
+fn kaiser_downsample(img: &Rgba32FImage, out_w: u32, out_h: u32) -> Rgba32FImage {
+    let (width, height) = img.dimensions();
+
+    // Computes source texel indices and normalized weights for a destination texel along one axis
+    let taps = |dst: u32, src_len: u32, dst_len: u32| -> Vec<(u32, f32)> {
+        let scale = src_len as f32 / dst_len as f32;
+        let center = (dst as f32 + 0.5) * scale;
+        let first = (center - 1.5 * scale).floor() as i64;
+        let last = (center + 1.5 * scale).ceil() as i64;
+        let mut taps: Vec<(u32, f32)> = (first..=last)
+            .map(|i| {
+                let weight = kaiser_weight((i as f32 + 0.5 - center) / scale);
+                (i.clamp(0, src_len as i64 - 1) as u32, weight)
+            })
+            .filter(|(_, w)| *w != 0.0)
+            .collect();
+        let total: f32 = taps.iter().map(|(_, w)| w).sum();
+        for tap in taps.iter_mut() {
+            tap.1 /= total;
+        }
+        taps
+    };
+
+    // Horizontal pass
+    let horizontal = Rgba32FImage::from_fn(out_w, height, |x, y| {
+        let mut out = [0.0f32; 4];
+        for (sx, w) in taps(x, width, out_w) {
+            let px = img.get_pixel(sx, y).0;
+            for c in 0..4 {
+                out[c] += px[c] * w;
+            }
+        }
+        Rgba(out)
+    });
+
+    // Vertical pass, clamping away the ringing of the sinc filter
+    return Rgba32FImage::from_fn(out_w, out_h, |x, y| {
+        let mut out = [0.0f32; 4];
+        for (sy, w) in taps(y, height, out_h) {
+            let px = horizontal.get_pixel(x, sy).0;
+            for c in 0..4 {
+                out[c] += px[c] * w;
+            }
+        }
+        Rgba(out.map(|v| v.clamp(0.0, 1.0)))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{level_count, mip_chain, MipFilter};
+    use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgb, RgbImage};
+
+    #[test]
+    fn chain_filters() {
+        assert_eq!(level_count(1, 1), 1);
+        assert_eq!(level_count(30, 7), 5);
+
+        // Max keeps a single bright texel, where box averages it away
+        let mut mask = GrayImage::new(4, 4);
+        mask.put_pixel(1, 1, Luma([255]));
+        let levels = mip_chain(DynamicImage::from(mask.clone()), MipFilter::Max, false);
+        assert_eq!(levels.len(), 3);
+        assert_eq!(levels[2].dimensions(), (1, 1));
+        assert_eq!(levels[2].to_luma8().get_pixel(0, 0).0, [255]);
+        let levels = mip_chain(DynamicImage::from(mask), MipFilter::Box, false);
+        assert_eq!(levels[1].to_luma8().get_pixel(0, 0).0, [64]);
+
+        // Averaging black and white in linear light is brighter than in sRGB
+        let checker = RgbImage::from_fn(2, 2, |x, y| Rgb([((x + y) % 2 * 255) as u8; 3]));
+        let levels = mip_chain(DynamicImage::from(checker), MipFilter::Box, true);
+        assert_eq!(levels[1].color(), image::ColorType::Rgb8);
+        assert_eq!(levels[1].to_rgb8().get_pixel(0, 0).0, [188; 3]);
+
+        // Opposing normals average to a unit vector, rather than shrinking
+        let normals = RgbImage::from_fn(2, 1, |x, _| match x {
+            0 => Rgb([255, 128, 128]), // +X
+            _ => Rgb([128, 128, 255]), // +Z
+        });
+        let levels = mip_chain(DynamicImage::from(normals), MipFilter::Normal, false);
+        let px = levels[1].to_rgb8().get_pixel(0, 0).0;
+        let v = px.map(|c| c as f32 / 255.0 * 2.0 - 1.0);
+        assert!(((v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt() - 1.0).abs() < 0.02);
+    }
+}
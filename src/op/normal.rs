@@ -0,0 +1,172 @@
+use crate::util::{self, Rgb16Image};
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, LumaA, Rgb, Rgb32FImage, Rgba};
+
+/// Channel layouts that normal maps are stored in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalLayout {
+    /// X, Y, and Z in red, green, and blue.
+    Xyz,
+    /// X and Y only, in the first two channels. Z is reconstructed by the shader.
+    Xy,
+    /// X in alpha and Y in green, as used by DXT5 (BC3) compressed normal maps. Red is 1, and blue is 0.
+    Dxt5nm,
+}
+
+impl NormalLayout {
+    /// Parses a normal layout name.
+    pub fn parse(name: &str) -> Option<NormalLayout> {
+        return match name {
+            "xyz" => Some(NormalLayout::Xyz),
+            "xy" => Some(NormalLayout::Xy),
+            "dxt5nm" => Some(NormalLayout::Dxt5nm),
+            _ => None,
+        };
+    }
+
+    /// Returns the color format that normal maps of this layout are stored in.
+    pub fn color_type(&self) -> ColorType {
+        return match self {
+            NormalLayout::Xyz => ColorType::Rgb16,
+            NormalLayout::Xy => ColorType::La16,
+            NormalLayout::Dxt5nm => ColorType::Rgba16,
+        };
+    }
+}
+
+/// Decodes an encoded normal texel in the range [0, 1] into a vector in the range [-1, 1].
+fn decode(texel: [f32; 3]) -> [f32; 3] {
+    return texel.map(|v| v * 2.0 - 1.0);
+}
+
+/// Normalizes a vector, and encodes it into the range [0, 1].
+/// Degenerate vectors are replaced with a flat normal, pointing straight out of the surface.
+fn encode_normalized(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if len < 1e-6 {
+        return [0.5, 0.5, 1.0];
+    }
+    return v.map(|c| (c / len) * 0.5 + 0.5);
+}
+
+/// Stores a normal map in the given layout, dropping Z for two channel layouts.
+pub fn encode_layout(img: Rgb16Image, layout: NormalLayout) -> DynamicImage {
+    return match layout {
+        NormalLayout::Xyz => DynamicImage::from(img),
+        NormalLayout::Xy => DynamicImage::from(ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            let px = img.get_pixel(x, y).0;
+            LumaA([px[0], px[1]])
+        })),
+        NormalLayout::Dxt5nm => DynamicImage::from(ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            let px = img.get_pixel(x, y).0;
+            Rgba([u16::MAX, px[1], 0, px[0]])
+        })),
+    };
+}
+
+/// Reads a normal map stored in the given layout, reconstructing Z for two channel layouts.
+/// For `Xy`, two channel images are read from luminance and alpha, and other images from red and green.
+pub fn decode_layout(img: DynamicImage, layout: NormalLayout) -> Rgb16Image {
+    let xy_channels: [usize; 2] = match layout {
+        NormalLayout::Xyz => return img.into_rgb16(),
+        NormalLayout::Xy if img.color().channel_count() == 2 => [0, 3],
+        NormalLayout::Xy => [0, 1],
+        NormalLayout::Dxt5nm => [3, 1],
+    };
+
+    // Two channel images expand luminance into red, green, and blue
+    let rgba = img.into_rgba32f();
+    let vectors = Rgb32FImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let px = rgba.get_pixel(x, y).0;
+        let nx = px[xy_channels[0]] * 2.0 - 1.0;
+        let ny = px[xy_channels[1]] * 2.0 - 1.0;
+        let nz = (1.0 - nx * nx - ny * ny).max(0.0).sqrt();
+        Rgb(encode_normalized([nx, ny, nz]))
+    });
+    return DynamicImage::from(vectors).into_rgb16();
+}
+
+/// Renormalizes every vector of a normal map, in place.
+pub fn renormalize(img: &mut Rgb32FImage) {
+    for pixel in img.pixels_mut() {
+        *pixel = Rgb(encode_normalized(decode(pixel.0)));
+    }
+}
+
+/// Resizes a normal map to the given maximum dimension (see [`util::auto_resize`]), renormalizing the resampled vectors.
+/// Resampling is done in floating point, as filtering the encoded texels is equivalent to filtering the decoded vectors.
+/// Images that are already the right size are returned as-is, without renormalizing.
+pub fn resize(img: DynamicImage, width: u32, height: u32) -> (Rgb16Image, u32, u32) {
+    let (dim_x, dim_y) = img.dimensions();
+    if util::fit_dimensions(dim_x, dim_y, width, height) == (dim_x, dim_y) {
+        return (img.into_rgb16(), dim_x, dim_y);
+    }
+    let (resized, width, height) = util::auto_resize(DynamicImage::from(img.into_rgb32f()), width, height);
+    let mut vectors = resized.into_rgb32f();
+    renormalize(&mut vectors);
+    return (DynamicImage::from(vectors).into_rgb16(), width, height);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_layout, encode_layout, renormalize, resize, NormalLayout};
+    use image::{DynamicImage, Rgb, Rgb32FImage, RgbImage};
+
+    /// Returns the length of an encoded normal texel.
+    fn length(px: [u16; 3]) -> f32 {
+        let v = px.map(|c| c as f32 / u16::MAX as f32 * 2.0 - 1.0);
+        return (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    }
+
+    #[test]
+    fn layout_round_trip() {
+        let normals = RgbImage::from_fn(4, 4, |x, y| {
+            let (nx, ny) = (x as f32 * 0.2 - 0.3, y as f32 * 0.2 - 0.3);
+            let nz = (1.0 - nx * nx - ny * ny).sqrt();
+            Rgb([nx, ny, nz].map(|v| ((v * 0.5 + 0.5) * 255.0).round() as u8))
+        });
+        let normals = DynamicImage::from(normals).into_rgb16();
+
+        for layout in [NormalLayout::Xyz, NormalLayout::Xy, NormalLayout::Dxt5nm] {
+            let encoded = encode_layout(normals.clone(), layout);
+            assert_eq!(encoded.color(), layout.color_type());
+            let decoded = decode_layout(encoded, layout);
+            for (a, b) in normals.pixels().zip(decoded.pixels()) {
+                for c in 0..3 {
+                    assert!((a[c] as i32 - b[c] as i32).abs() < 1024, "{0:?}: {1:?} != {2:?}", layout, a, b);
+                }
+            }
+        }
+
+        // Red and green sources, such as an RG texture saved as RGB
+        let rg = RgbImage::from_pixel(1, 1, Rgb([128, 255, 0]));
+        let decoded = decode_layout(DynamicImage::from(rg), NormalLayout::Xy);
+        assert!(length(decoded.get_pixel(0, 0).0) > 0.99);
+        assert!(decoded.get_pixel(0, 0)[2] < 40000);
+    }
+
+    #[test]
+    fn resize_unit_vectors() {
+        // Alternating normals tilted left and right, which average to a short vector when filtered as colors
+        let img = RgbImage::from_fn(8, 8, |x, _| match x % 2 {
+            0 => Rgb([37, 128, 218]),
+            _ => Rgb([218, 128, 218]),
+        });
+        let (resized, width, height) = resize(DynamicImage::from(img), 4, 4);
+        assert_eq!((width, height), (4, 4));
+        for px in resized.pixels() {
+            assert!((length(px.0) - 1.0).abs() < 0.01, "{0:?} is not unit length", px.0);
+        }
+
+        // Denormalized and degenerate source vectors
+        let mut img = Rgb32FImage::from_fn(2, 1, |x, _| match x {
+            0 => Rgb([0.75, 0.5, 0.75]),
+            _ => Rgb([0.5, 0.5, 0.5]),
+        });
+        let (unchanged, _, _) = resize(DynamicImage::from(img.clone()), 2, 2);
+        assert_eq!(unchanged, DynamicImage::from(img.clone()).into_rgb16());
+        renormalize(&mut img);
+        let px = img.get_pixel(0, 0).0;
+        assert!((px[0] - (0.5 + 0.5 / 2f32.sqrt())).abs() < 1e-5);
+        assert_eq!(img.get_pixel(1, 0).0, [0.5, 0.5, 1.0]);
+    }
+}
@@ -1,9 +1,269 @@
 use image::{ImageBuffer, Rgba, Rgba32FImage};
 
-/// Provided a set of images, packs the Red channel of each image into the corresponding RGBA channels of a new one.
+/// Names of the destination channels of a packed texture, in order.
+pub const SLOT_NAMES: [&str; 4] = ["r", "g", "b", "a"];
+
+/// Channel of a source image that is read when packing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceChannel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    /// Rec. 709 luminance of the RGB channels.
+    Luminance,
+}
+
+impl SourceChannel {
+    /// Parses a source channel name (`r`, `g`, `b`, `a`, or `l`, or their full names).
+    pub fn parse(name: &str) -> Option<SourceChannel> {
+        return match name.to_lowercase().as_str() {
+            "r" | "red" => Some(SourceChannel::Red),
+            "g" | "green" => Some(SourceChannel::Green),
+            "b" | "blue" => Some(SourceChannel::Blue),
+            "a" | "alpha" => Some(SourceChannel::Alpha),
+            "l" | "luma" | "luminance" => Some(SourceChannel::Luminance),
+            _ => None,
+        };
+    }
+
+    /// Reads this channel from a pixel.
+    pub fn sample(&self, pixel: &Rgba<f32>) -> f32 {
+        return match self {
+            SourceChannel::Red => pixel.0[0],
+            SourceChannel::Green => pixel.0[1],
+            SourceChannel::Blue => pixel.0[2],
+            SourceChannel::Alpha => pixel.0[3],
+            SourceChannel::Luminance => {
+                0.2126 * pixel.0[0] + 0.7152 * pixel.0[1] + 0.0722 * pixel.0[2]
+            }
+        };
+    }
+}
+
+/// Returns the value a map is assumed to have when its source texture is missing.
+/// Occlusion, opacity, and detail masks default to white (unoccluded, opaque, full detail), roughness to half-rough, and everything else to black.
+pub fn default_value(map: &str) -> f32 {
+    return match map {
+        "ao" | "occlusion" | "opacity" | "alpha" | "detail" => 1.0,
+        "roughness" => 0.5,
+        _ => 0.0,
+    };
+}
+
+/// A single destination channel of a packed texture, where its data comes from, and how it is transformed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackSlot {
+    /// Name of the source map, such as `roughness`. `None` fills the channel with the default value.
+    pub map: Option<String>,
+    /// Channel of the source map to read.
+    pub channel: SourceChannel,
+    /// Whether to invert the source value (`1 - x`), such as for converting roughness to smoothness.
+    pub invert: bool,
+    /// Multiplier applied to the source value, after inversion.
+    pub scale: f32,
+    /// Offset added to the source value, after scaling.
+    pub bias: f32,
+    /// Range the final value is clamped to, if any.
+    pub clamp: Option<(f32, f32)>,
+    /// Source value used in place of the source map when it is missing. Transforms still apply.
+    pub default: f32,
+}
+
+impl PackSlot {
+    /// Creates a new slot reading the given channel of a source map, with no transforms.
+    pub fn new(map: &str, channel: SourceChannel) -> PackSlot {
+        return PackSlot {
+            map: Some(map.to_string()),
+            channel,
+            invert: false,
+            scale: 1.0,
+            bias: 0.0,
+            clamp: None,
+            default: default_value(map),
+        };
+    }
+
+    /// Creates a new slot that is filled with a constant value.
+    pub fn constant(value: f32) -> PackSlot {
+        return PackSlot {
+            map: None,
+            channel: SourceChannel::Luminance,
+            invert: false,
+            scale: 1.0,
+            bias: 0.0,
+            clamp: None,
+            default: value,
+        };
+    }
+
+    /// Returns a copy of this slot with inversion enabled.
+    pub fn inverted(mut self) -> PackSlot {
+        self.invert = true;
+        return self;
+    }
+
+    /// Parses a slot from one of:
+    /// - a map name with an optional channel suffix, `"basecolor.a"`
+    /// - a constant value, `0.5`
+    /// - an object, `{ "map": "roughness", "channel": "r", "invert": true, "scale": 1.0, "bias": 0.0, "clamp": [0.0, 1.0], "default": 0.5 }`,
+    ///   where every field is optional, though a constant slot must declare a `default`
+    ///
+    /// Source channels default to luminance if unspecified.
+    pub fn from_json(value: &json::JsonValue) -> Option<PackSlot> {
+        if let Some(name) = value.as_str() {
+            if let Some((map, channel)) = name.rsplit_once('.') {
+                if let Some(channel) = SourceChannel::parse(channel) {
+                    return Some(PackSlot::new(map, channel));
+                }
+            }
+            return Some(PackSlot::new(name, SourceChannel::Luminance));
+        }
+
+        if let Some(constant) = value.as_f32() {
+            return Some(PackSlot::constant(constant));
+        }
+
+        if !value.is_object() {
+            return None;
+        }
+
+        let mut slot = match value["map"].as_str() {
+            Some(map) => PackSlot::new(map, SourceChannel::Luminance),
+            None if value.has_key("map") || !value.has_key("default") => return None,
+            None => PackSlot::constant(0.0),
+        };
+        if value.has_key("channel") {
+            slot.channel = SourceChannel::parse(value["channel"].as_str()?)?;
+        }
+        if value.has_key("invert") {
+            slot.invert = value["invert"].as_bool()?;
+        }
+        if value.has_key("scale") {
+            slot.scale = value["scale"].as_f32()?;
+        }
+        if value.has_key("bias") {
+            slot.bias = value["bias"].as_f32()?;
+        }
+        if value.has_key("clamp") {
+            let range = &value["clamp"];
+            if range.len() != 2 {
+                return None;
+            }
+            slot.clamp = Some((range[0].as_f32()?, range[1].as_f32()?));
+        }
+        if value.has_key("default") {
+            slot.default = value["default"].as_f32()?;
+        }
+        return Some(slot);
+    }
+
+    /// Applies this slot's transforms to a source value.
+    pub fn transform(&self, mut value: f32) -> f32 {
+        if self.invert {
+            value = 1.0 - value;
+        }
+        value = value * self.scale + self.bias;
+        if let Some((min, max)) = self.clamp {
+            value = value.clamp(min, max);
+        }
+        return value;
+    }
+
+    /// Undoes this slot's transforms on a packed value, recovering the source value.
+    /// Clamping cannot be undone, and is ignored.
+    pub fn untransform(&self, mut value: f32) -> f32 {
+        value -= self.bias;
+        if self.scale != 0.0 {
+            value /= self.scale;
+        }
+        if self.invert {
+            value = 1.0 - value;
+        }
+        return value;
+    }
+}
+
+/// Describes which source map is packed into each of the RGBA channels of a packed texture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackLayout {
+    /// Source of each destination channel (R, G, B, A). `None` leaves the channel empty.
+    pub slots: [Option<PackSlot>; 4],
+}
+
+impl PackLayout {
+    /// Creates a new layout that reads the luminance of the given source maps into each destination channel.
+    pub fn new(r: Option<&str>, g: Option<&str>, b: Option<&str>, a: Option<&str>) -> PackLayout {
+        return PackLayout {
+            slots: [r, g, b, a].map(|s| s.map(|v| PackSlot::new(v, SourceChannel::Luminance))),
+        };
+    }
+
+    /// Creates a new layout from a slot for each destination channel.
+    pub fn from_slots(slots: [Option<PackSlot>; 4]) -> PackLayout {
+        return PackLayout {
+            slots,
+        };
+    }
+
+    /// Parses a layout from a JSON object of the form `{ "r": "ao", "g": "roughness", "b": "metallic" }`.
+    /// See [`PackSlot::from_json`] for the format of each channel.
+    /// Returns `None` if the value is not a valid layout.
+    pub fn from_json(value: &json::JsonValue) -> Option<PackLayout> {
+        if !value.is_object() {
+            return None;
+        }
+
+        let mut layout = PackLayout::from_slots([None, None, None, None]);
+        for (idx, slot) in SLOT_NAMES.iter().enumerate() {
+            if value.has_key(slot) {
+                layout.slots[idx] = Some(PackSlot::from_json(&value[*slot])?);
+            }
+        }
+
+        // A layout with no channels is meaningless
+        if layout.slots.iter().all(|s| s.is_none()) {
+            return None;
+        }
+        return Some(layout);
+    }
+
+    /// Finds the destination channel index and slot that the given source map is packed into, if any.
+    pub fn find(&self, map: &str) -> Option<(usize, &PackSlot)> {
+        return self.slots.iter().enumerate().find_map(|(idx, slot)| {
+            slot.as_ref()
+                .filter(|slot| slot.map.as_deref() == Some(map))
+                .map(|slot| (idx, slot))
+        });
+    }
+
+    /// Returns true if this layout writes into the alpha channel.
+    pub fn has_alpha(&self) -> bool {
+        return self.slots[3].is_some();
+    }
+}
+
+/// Returns a built-in packing layout by name, if one exists.
+/// - `arm` / `orm`: ambient occlusion, roughness, metallic in RGB.
+/// - `mra`: Unity HDRP mask map; metallic, ambient occlusion, detail mask, smoothness (inverted roughness) in RGBA.
+pub fn preset(name: &str) -> Option<PackLayout> {
+    return match name {
+        "arm" | "orm" => Some(PackLayout::new(Some("ao"), Some("roughness"), Some("metallic"), None)),
+        "mra" => Some(PackLayout::from_slots([
+            Some(PackSlot::new("metallic", SourceChannel::Luminance)),
+            Some(PackSlot::new("ao", SourceChannel::Luminance)),
+            Some(PackSlot::new("detail", SourceChannel::Luminance)),
+            Some(PackSlot::new("roughness", SourceChannel::Luminance).inverted()),
+        ])),
+        _ => None,
+    };
+}
+
+/// Provided a source image for each slot of the layout, packs the selected and transformed channel of each into the corresponding RGBA channels of a new one.
+/// Slots with a missing source image use the slot's default value. Unused slots are left empty, except for alpha which is maximized.
 pub fn channel_pack(
-    channel_data: Vec<Rgba32FImage>,
-    use_alpha: bool,
+    channel_data: Vec<Option<Rgba32FImage>>,
+    layout: &PackLayout,
     width: u32,
     height: u32,
 ) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
@@ -11,17 +271,94 @@ pub fn channel_pack(
     let mut imgbuf = image::ImageBuffer::new(width, height);
     // Fill out pixels of new image with data from inputs
     for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-        let r = channel_data[0].get_pixel(x, y).0[0] as f32;
-        let g = channel_data[1].get_pixel(x, y).0[1] as f32;
-        let b = channel_data[2].get_pixel(x, y).0[2] as f32;
-        let mut a = channel_data[3].get_pixel(x, y).0[3] as u8;
-        if !use_alpha {
-            // If we're not using alpha, maximize the alpha channel
-            a = 255 as u8;
+        let mut out = [0.0, 0.0, 0.0, 1.0];
+        for (idx, slot) in layout.slots.iter().enumerate() {
+            if let Some(slot) = slot {
+                let value = match &channel_data[idx] {
+                    Some(img) => slot.channel.sample(img.get_pixel(x, y)),
+                    None => slot.default,
+                };
+                out[idx] = slot.transform(value);
+            }
         }
 
-        *pixel = Rgba([r, g, b, a.into()]);
+        *pixel = Rgba(out);
     }
 
     return imgbuf;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{channel_pack, preset, PackLayout, PackSlot, SourceChannel};
+    use image::{Rgba, Rgba32FImage};
+
+    #[test]
+    fn parse_layout() {
+        let layout = PackLayout::from_json(&json::object! { r: "metallic", a: "smoothness" })
+            .expect("Parse valid layout");
+        assert_eq!(
+            layout,
+            PackLayout::new(Some("metallic"), None, None, Some("smoothness"))
+        );
+        assert!(layout.has_alpha());
+
+        // Empty layouts and malformed sources are rejected
+        assert!(PackLayout::from_json(&json::object! {}).is_none());
+        assert!(PackLayout::from_json(&json::object! { r: true }).is_none());
+
+        // ARM is a built-in preset
+        assert_eq!(preset("arm"), preset("orm"));
+        assert!(!preset("arm").expect("ARM preset").has_alpha());
+        // Missing detail masks keep full detail
+        let mra = preset("mra").expect("MRA preset");
+        assert_eq!(mra.slots[2].as_ref().map(|slot| slot.default), Some(1.0));
+    }
+
+    #[test]
+    fn pack_source_channels() {
+        let layout = PackLayout::from_json(&json::object! {
+            r: "basecolor.a",
+            g: { map: "packed", channel: "g" },
+            b: "roughness",
+        })
+        .expect("Parse valid layout");
+        assert_eq!(
+            layout.slots[0],
+            Some(PackSlot::new("basecolor", SourceChannel::Alpha))
+        );
+        assert!(PackLayout::from_json(&json::object! { r: { map: "a", channel: "x" } }).is_none());
+
+        let basecolor = Rgba32FImage::from_pixel(1, 1, Rgba([0.1, 0.2, 0.3, 0.4]));
+        let packed = Rgba32FImage::from_pixel(1, 1, Rgba([0.0, 0.6, 0.0, 1.0]));
+        let roughness = Rgba32FImage::from_pixel(1, 1, Rgba([0.5, 0.5, 0.5, 1.0]));
+        let out = channel_pack(
+            vec![Some(basecolor), Some(packed), Some(roughness), None],
+            &layout,
+            1,
+            1,
+        );
+        let px = out.get_pixel(0, 0).0;
+        assert_eq!([px[0], px[1], px[3]], [0.4, 0.6, 1.0]);
+        assert!((px[2] - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn pack_transforms() {
+        let layout = PackLayout::from_json(&json::object! {
+            r: "ao",
+            g: { map: "roughness", invert: true },
+            b: { map: "metallic", scale: 2.0, bias: -0.5, clamp: [0.0, 1.0] },
+            a: 0.25,
+        })
+        .expect("Parse valid layout");
+        assert!(PackLayout::from_json(&json::object! { r: { invert: true } }).is_none());
+
+        let roughness = Rgba32FImage::from_pixel(1, 1, Rgba([0.75, 0.75, 0.75, 1.0]));
+        let metallic = Rgba32FImage::from_pixel(1, 1, Rgba([1.0, 1.0, 1.0, 1.0]));
+        let out = channel_pack(vec![None, Some(roughness), Some(metallic), None], &layout, 1, 1);
+
+        // Missing AO is unoccluded, roughness becomes smoothness, metallic is clamped, alpha is constant
+        assert_eq!(out.get_pixel(0, 0).0, [1.0, 0.25, 1.0, 0.25]);
+    }
+}
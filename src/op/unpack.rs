@@ -0,0 +1,38 @@
+use crate::op::pack::PackSlot;
+use image::{Rgba, Rgba32FImage};
+
+/// Extracts a single channel of a packed texture into a new grayscale image (stored as RGBA with full alpha),
+/// undoing the transforms of the slot that packed it.
+pub fn channel_unpack(packed: &Rgba32FImage, index: usize, slot: &PackSlot) -> Rgba32FImage {
+    let (width, height) = packed.dimensions();
+
+    // Prep new image
+    let mut imgbuf = image::ImageBuffer::new(width, height);
+    // Fill out pixels of new image with the unpacked channel
+    for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
+        let value = slot.untransform(packed.get_pixel(x, y).0[index]);
+        *pixel = Rgba([value, value, value, 1.0]);
+    }
+
+    return imgbuf;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::channel_unpack;
+    use crate::op::pack::{channel_pack, preset};
+    use image::{Rgba, Rgba32FImage};
+
+    #[test]
+    fn unpack_round_trip() {
+        let mra = preset("mra").expect("MRA preset");
+        let roughness = Rgba32FImage::from_pixel(1, 1, Rgba([0.25, 0.25, 0.25, 1.0]));
+        let packed = channel_pack(vec![None, None, None, Some(roughness)], &mra, 1, 1);
+
+        // Smoothness is stored in alpha, and should unpack back to roughness
+        let (index, slot) = mra.find("roughness").expect("Find roughness in MRA");
+        assert_eq!(index, 3);
+        let unpacked = channel_unpack(&packed, index, slot);
+        assert_eq!(unpacked.get_pixel(0, 0).0, [0.25, 0.25, 0.25, 1.0]);
+    }
+}
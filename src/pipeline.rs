@@ -1,220 +1,666 @@
-use crate::{op, util};
-use image::{ColorType, DynamicImage, Rgba32FImage};
+use crate::format::output::{self, OutputFormat, PixelFormat};
+use crate::op::mip::MipFilter;
+use crate::op::normal::NormalLayout;
+use crate::op::pack::PackLayout;
+use crate::cli::args::BuildOptions;
+use crate::cache::{Fnv1a, Manifest};
+use crate::cli::verbosity;
+use crate::error::{Error, Result};
+use crate::model::{self, Diagnostics, PipelineConfig};
+use crate::naming::{Naming, SourceDir};
+use crate::report::{self, MapReport, Status};
+use crate::{budget, inspect, op, pool, util, validate};
+use image::{imageops, ColorType, DynamicImage, GenericImageView, Rgba32FImage};
 use std::cmp::min;
-use std::fs::read_to_string;
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::thread;
-use std::thread::JoinHandle;
 use std::time;
-use std::{fs, path};
 
-/// Loads, (optionally) packs, converts, and compresses a single image from the given parameters.
-fn threaded_convert(
-    input_dir: String,
-    outdir: String,
+/// Parameters for converting a single texture map of a material.
+struct MapJob {
+    /// Listing of the input directory, shared by every map of the build.
+    input_dir: Arc<SourceDir>,
+    output_dir: PathBuf,
     material: String,
     channel: String,
     resolution: u32,
     flip_green: bool,
+    /// Whether to renormalize normal map vectors, for sources that are not already unit length.
+    renormalize: bool,
+    /// Channel layout of source normal maps.
+    normal_source: NormalLayout,
     has_alpha: bool,
+    /// Packing layout to use, if this channel is a packed texture.
+    layout: Option<PackLayout>,
+    /// Packed source textures, and their layouts, that source maps can be unpacked from.
+    unpack: Vec<(String, PackLayout)>,
+    format: OutputFormat,
+    /// File name conventions of source and output textures.
+    naming: Naming,
+    /// Color space overrides by map name, where `true` is sRGB. See [`MapJob::is_srgb`].
+    color_spaces: HashMap<String, bool>,
+    /// Filter for downsampling mip levels.
+    mip_filter: MipFilter,
     dryrun: bool,
-) -> JoinHandle<()> {
-    return thread::spawn(move || {
-        // Generate file paths from strings (since we can't copy them from threads)
-        let input_dir = path::PathBuf::from(input_dir);
-        let output_dir = path::PathBuf::from(outdir);
-
-        // Load defaults
-        let mut ct = util::map_to_color(channel.as_str());
-        let mut out_img: DynamicImage; //= image::DynamicImage::new_luma8(resolution, resolution); // Create blank template image
-        let out_path = util::path_material_map(
-            output_dir.as_path(),
-            material.as_str(),
-            channel.as_str(),
-            "png",
-        );
-        let mut width: u32 = resolution;
-        let mut height: u32 = resolution;
+}
 
-        // Forcibly include alpha in basecolor pass if we were told to
-        if has_alpha && channel.eq("basecolor") {
-            ct = ColorType::Rgba8;
+impl MapJob {
+    /// Returns true if the given map stores sRGB encoded color, unless overridden by the pipeline.
+    fn is_srgb(&self, map: &str) -> bool {
+        return self.color_spaces.get(map).copied().unwrap_or_else(|| util::map_is_srgb(map));
+    }
+
+    /// Returns the path of every source texture this map could be built from, whether or not it exists.
+    fn source_paths(&self) -> Vec<PathBuf> {
+        let mut maps: Vec<&String> = vec![&self.channel];
+        if let Some(layout) = self.layout.as_ref() {
+            maps.extend(layout.slots.iter().flatten().filter_map(|slot| slot.map.as_ref()));
         }
+        maps.extend(self.unpack.iter().map(|(source, _)| source));
+        return maps
+            .into_iter()
+            .map(|map| self.naming.source_path(&self.input_dir, &self.material, map))
+            .collect();
+    }
 
-        let base_path = util::path_material_map(
-            input_dir.as_path(),
-            material.as_str(),
-            channel.as_str(),
-            "png",
+    /// Returns the path of every source texture this map is built from. See [`channel_sources`].
+    fn sources(&self) -> Vec<PathBuf> {
+        return channel_sources(
+            &self.input_dir,
+            &self.material,
+            &self.channel,
+            self.layout.as_ref(),
+            &self.unpack,
+            &self.naming,
         );
-        if channel.eq("arm") && !base_path.exists() {
-            // Only load basemaps for ARM if there isn't an existing ARM texture
-            let base_path_ao =
-                util::path_material_map(input_dir.as_path(), material.as_str(), "ao", "png");
-            let base_path_rough =
-                util::path_material_map(input_dir.as_path(), material.as_str(), "roughness", "png");
-            let base_path_metal =
-                util::path_material_map(input_dir.as_path(), material.as_str(), "metallic", "png");
-
-            // TODO: I don't like storing all these as 32F images. Large and hard to work with.
-            let map_ao: Rgba32FImage;
-            let map_rough: Rgba32FImage;
-            let map_metal: Rgba32FImage;
-
-            // TODO: remove duplication here if possible?
-            if base_path_ao.exists() {
-                let (m, w, h) =
-                    util::load_image_adv(base_path_ao.as_path(), resolution, ColorType::Rgba32F);
-                width = min(width, w);
-                height = min(height, h);
-                map_ao = m.into_rgba32f();
-            } else {
-                map_ao = DynamicImage::new_rgba32f(width, height).into_rgba32f();
-            }
-            if base_path_rough.exists() {
-                let (m, w, h) =
-                    util::load_image_adv(base_path_rough.as_path(), resolution, ColorType::Rgba32F);
-                width = min(width, w);
-                height = min(height, h);
-                map_rough = m.into_rgba32f();
-            } else {
-                map_rough = DynamicImage::new_rgba32f(width, height).into_rgba32f();
-            }
-            if base_path_metal.exists() {
-                let (m, w, h) =
-                    util::load_image_adv(base_path_metal.as_path(), resolution, ColorType::Rgba32F);
-                width = min(width, w);
-                height = min(height, h);
-                map_metal = m.into_rgba32f();
-            } else {
-                map_metal = DynamicImage::new_rgba32f(width, height).into_rgba32f();
-            }
+    }
+
+    /// Returns a hash of everything the output of this map depends on:
+    /// the version of IronPress, the settings of the map, and the contents of its source textures.
+    fn input_hash(&self) -> String {
+        let mut color_spaces: Vec<(&String, &bool)> = self.color_spaces.iter().collect();
+        color_spaces.sort();
+
+        let mut hasher = Fnv1a::default();
+        hasher.write_str(env!("CARGO_PKG_VERSION"));
+        hasher.write_str(&format!(
+            "{0:?}",
+            (
+                self.resolution,
+                self.flip_green,
+                self.renormalize,
+                self.normal_source,
+                self.has_alpha,
+                &self.layout,
+                &self.unpack,
+                self.format,
+                color_spaces,
+                self.mip_filter,
+            )
+        ));
+        for path in self.source_paths() {
+            hasher.write_file(&path);
+        }
+        return hasher.finish();
+    }
+
+    /// Returns a rough estimate of the memory converting this map takes, in bytes.
+    /// Source textures are decoded at full size and converted to 32-bit floats, and the output is held alongside its mip chain.
+    fn memory_estimate(&self) -> u64 {
+        let sources: u64 = self
+            .source_paths()
+            .iter()
+            .filter_map(|path| image::image_dimensions(path).ok())
+            .map(|(width, height)| width as u64 * height as u64)
+            .sum();
+        let output = self.resolution as u64 * self.resolution as u64;
+        return (sources + output * 2) * 16;
+    }
+}
+
+/// Returns the path of a source texture for a map of a material, either its own texture or the first packed source that contains it.
+fn source_of(
+    input_dir: &SourceDir,
+    material: &str,
+    map: &str,
+    unpack: &[(String, PackLayout)],
+    naming: &Naming,
+) -> Option<PathBuf> {
+    if let Some(path) = naming.source(input_dir, material, map) {
+        return Some(path);
+    }
+    return unpack
+        .iter()
+        .filter(|(_, layout)| layout.find(map).is_some())
+        .find_map(|(source, _)| naming.source(input_dir, material, source));
+}
+
+/// Returns the path of every existing source texture a channel of a material is built from, in the same order they are loaded.
+pub fn channel_sources(
+    input_dir: &SourceDir,
+    material: &str,
+    channel: &str,
+    layout: Option<&PackLayout>,
+    unpack: &[(String, PackLayout)],
+    naming: &Naming,
+) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = Vec::new();
+    let maps: Vec<&str> = match layout {
+        // Packed textures use an existing packed texture as-is if present
+        Some(layout) if source_of(input_dir, material, channel, unpack, naming).is_none() => {
+            layout.slots.iter().flatten().filter_map(|slot| slot.map.as_deref()).collect()
+        }
+        _ => vec![channel],
+    };
+    for path in maps.into_iter().filter_map(|map| source_of(input_dir, material, map, unpack, naming)) {
+        if !sources.contains(&path) {
+            sources.push(path);
+        }
+    }
+    return sources;
+}
+
+/// A map queued for conversion.
+struct PendingMap {
+    /// File name of the output texture.
+    output: String,
+    /// Hash of the inputs of the map. See [`MapJob::input_hash`].
+    key: String,
+    /// Index of the report of the map.
+    report: usize,
+    /// Pixel format the map takes in GPU memory.
+    pixel_format: PixelFormat,
+    /// Whether the map includes a mip chain.
+    mips: bool,
+}
+
+/// Dimensions of a converted map, and the time it took to convert.
+struct Converted {
+    width: u32,
+    height: u32,
+    millis: u128,
+}
+
+/// Loads a source map of the material, converted to the given color format.
+/// If the map has no texture of its own, it is unpacked from the first declared packed source that contains it.
+/// Returns `None` if there is no source for the map.
+fn load_source(job: &MapJob, map: &str, ct: ColorType) -> Result<Option<(DynamicImage, u32, u32)>> {
+    if let Some(path) = job.naming.source(&job.input_dir, &job.material, map) {
+        if map == "normal" {
+            // Normals are resampled as vectors, so they stay unit length
+            let normals = op::normal::decode_layout(util::load_image(&path)?, job.normal_source);
+            let (img, width, height) = op::normal::resize(DynamicImage::from(normals), job.resolution, job.resolution);
+            return Ok(Some((DynamicImage::from(img), width, height)));
+        }
+        return util::load_image_adv(&path, job.resolution, ct, job.is_srgb(map)).map(Some);
+    }
 
-            // TODO: simplify conversion process if possible
-            let maps = vec![
-                map_ao,
-                map_rough,
-                map_metal,
-                image::DynamicImage::new_rgba32f(width, height).into_rgba32f(),
-            ];
-            let arm = op::pack::channel_pack(maps, false, width, height);
-            out_img = DynamicImage::from(DynamicImage::from(arm).into_rgb8());
-            ct = ColorType::Rgb8; // Override color space
+    for (source, layout) in job.unpack.iter() {
+        let Some((index, slot)) = layout.find(map) else {
+            continue;
+        };
+        let Some(path) = job.naming.source(&job.input_dir, &job.material, source) else {
+            continue;
+        };
+
+        let (packed, width, height) = util::load_image_adv(&path, job.resolution, ColorType::Rgba32F, false)?;
+        let unpacked = op::unpack::channel_unpack(&packed.into_rgba32f(), index, slot);
+        return Ok(Some((util::convert_color(DynamicImage::from(unpacked), ct), width, height)));
+    }
+
+    return Ok(None);
+}
+
+/// Loads each source map of a packing layout and packs them into a single image.
+/// Missing source maps are filled with the default value of their slot.
+fn pack_layout(job: &MapJob, layout: &PackLayout) -> Result<(DynamicImage, u32, u32)> {
+    let mut width: u32 = job.resolution;
+    let mut height: u32 = job.resolution;
+
+    // Load all available source maps first, so we know the final dimensions
+    let mut sources: Vec<Option<DynamicImage>> = Vec::new();
+    for slot in layout.slots.iter() {
+        let Some(map) = slot.as_ref().and_then(|slot| slot.map.as_ref()) else {
+            sources.push(None);
+            continue;
+        };
+        let source = load_source(job, map, ColorType::Rgba32F)?.map(|(m, w, h)| {
+            width = min(width, w);
+            height = min(height, h);
+            m
+        });
+        sources.push(source);
+    }
+
+    // TODO: I don't like storing all these as 32F images. Large and hard to work with.
+    let maps: Vec<Option<Rgba32FImage>> = sources
+        .into_iter()
+        .map(|source| {
+            source.map(|m| match m.dimensions() == (width, height) {
+                true => m.into_rgba32f(),
+                false => m
+                    .resize_exact(width, height, imageops::FilterType::Lanczos3)
+                    .into_rgba32f(),
+            })
+        })
+        .collect();
+
+    let packed = DynamicImage::from(op::pack::channel_pack(maps, layout, width, height));
+    if layout.has_alpha() {
+        return Ok((DynamicImage::from(packed.into_rgba8()), width, height));
+    }
+    return Ok((DynamicImage::from(packed.into_rgb8()), width, height));
+}
+
+/// Returns the color format a map is processed in, before any normal map layout is applied.
+pub fn output_color(channel: &str, layout: Option<&PackLayout>, has_alpha: bool) -> ColorType {
+    if let Some(layout) = layout {
+        // Packed textures override the color format of the map
+        return if layout.has_alpha() { ColorType::Rgba8 } else { ColorType::Rgb8 };
+    }
+    if has_alpha && channel.eq("basecolor") {
+        // Forcibly include alpha in basecolor pass if we were told to
+        return ColorType::Rgba8;
+    }
+    return util::map_to_color(channel);
+}
+
+/// Returns true if every file of an output texture exists, including each mip level of PNG outputs with mips.
+fn outputs_exist(path: &Path, format: &OutputFormat) -> bool {
+    if !path.exists() {
+        return false;
+    }
+    if format.container != output::Container::Png || !format.mips() {
+        return true;
+    }
+    let Some((width, height)) = inspect::dimensions(path) else {
+        return false;
+    };
+    let levels = op::mip::level_count(width, height) as usize;
+    return (1..levels).all(|level| output::mip_path(path, level).exists());
+}
+
+/// Loads, (optionally) packs, converts, and compresses a single image from the given parameters.
+fn convert(job: MapJob) -> Result<Converted> {
+    let time_start = time::Instant::now();
+
+    // Load defaults
+    let ct = output_color(&job.channel, job.layout.as_ref(), job.has_alpha);
+    let mut out_img: DynamicImage;
+    let out_path = job.naming.output_path(
+        job.output_dir.as_path(),
+        job.material.as_str(),
+        job.channel.as_str(),
+        job.format.extension(),
+    );
+
+    let base_path = job.naming.source_path(&job.input_dir, job.material.as_str(), job.channel.as_str());
+    if let Some(layout) = job.layout.as_ref() {
+        // Packed textures use an existing packed texture as-is if present, otherwise they're built from source maps
+        if base_path.exists() {
+            (out_img, _, _) = util::load_image_adv(base_path.as_path(), job.resolution, ct, job.is_srgb(&job.channel))?;
         } else {
-            // Otherwise, use default process
-            if !base_path.exists() {
-                // Exit thread if path isn't found
-                eprintln!("\tFILE NOT FOUND at {0}", base_path.to_str().unwrap());
-                return;
-            }
-            let (m, w, h) = util::load_image_adv(base_path.as_path(), resolution, ct);
-            out_img = m;
-            width = w;
-            height = h;
+            (out_img, _, _) = pack_layout(&job, layout)?;
         }
+    } else {
+        // Otherwise, use default process
+        match load_source(&job, &job.channel, ct)? {
+            Some((img, _, _)) => out_img = img,
+            None => return Err(Error::MissingSource { path: base_path }),
+        }
+    }
 
-        // If requested and this is a normal map, invert green channel
-        if flip_green && channel.eq("normal") {
+    // If requested and this is a normal map, invert green channel and renormalize
+    if job.channel.eq("normal") {
+        if job.flip_green {
             out_img = DynamicImage::from(op::flip::flip_green(out_img.into_rgb16()));
         }
+        if job.renormalize {
+            let mut vectors = out_img.into_rgb32f();
+            op::normal::renormalize(&mut vectors);
+            out_img = util::convert_color(DynamicImage::from(vectors), ColorType::Rgb16);
+        }
+    }
 
-        if dryrun {
+    if job.dryrun {
+        if !verbosity::is_quiet() {
             println!("\tProcessed {0}", out_path.to_str().unwrap());
-            return;
         }
+        let (width, height) = out_img.dimensions();
+        return Ok(Converted { width, height, millis: time_start.elapsed().as_millis() });
+    }
 
-        // Save out image
-        util::compressed_save(out_path.as_path(), out_img.as_bytes(), width, height, ct.into());
+    // Save out image
+    let srgb = job.is_srgb(&job.channel);
+    let is_normal = job.channel.eq("normal");
+    let (width, height) = out_img.dimensions();
+    output::save(out_path.as_path(), out_img, ct, srgb, &job.format, job.mip_filter, is_normal)?;
+    if verbosity::is_verbose() {
+        println!(
+            "\tExported {0} ({1}x{2} {3:?}, in {4} ms)",
+            out_path.to_str().unwrap(),
+            width,
+            height,
+            ct,
+            time_start.elapsed().as_millis()
+        );
+    } else if !verbosity::is_quiet() {
         println!("\tExported {0}", out_path.to_str().unwrap());
-    });
+    }
+    return Ok(Converted { width, height, millis: time_start.elapsed().as_millis() });
 }
 
-/// Loads an IronPress Pipeline JSON file and converts spawns a thread for converting each material, awaiting until all threads are completed.  
-pub fn from_file(config_file: &Path, dryrun: bool) {
-    let dir = config_file.parent().unwrap(); // Get working directory
+/// Loads and validates an IronPress Pipeline JSON file, printing any problems.
+pub fn load_config(config_file: &Path) -> Option<PipelineConfig> {
+    // Load, parse, and validate configuration, reporting every problem at once
+    let mut diagnostics = Diagnostics::default();
+    let config = model::load(config_file, &mut diagnostics);
+    diagnostics.print();
+    if config.is_none() {
+        eprintln!(
+            "Pipeline at {0} has {1} error(s), aborting",
+            config_file.to_str().unwrap(),
+            diagnostics.errors.len()
+        );
+    }
+    return config;
+}
+
+/// Returns the output directory of a pipeline, which is relative to the pipeline file unless overridden.
+/// An override given on the command line is relative to the working directory instead.
+pub fn output_dir(config_file: &Path, config: &PipelineConfig, output: Option<&String>) -> PathBuf {
+    return match output {
+        Some(output) => PathBuf::from(output),
+        None => config_file.parent().unwrap().join(Path::new(&config.output)),
+    };
+}
+
+/// Returns the pipeline as built for each requested profile, paired with the name of the profile.
+/// Without any requested profiles, only the pipeline itself is built. Returns `None` if a requested profile does not exist.
+pub fn targets(config: &PipelineConfig, profiles: &[String], all: bool) -> Option<Vec<(Option<String>, PipelineConfig)>> {
+    let names: Vec<&String> = match all {
+        true => config.profiles.iter().map(|profile| &profile.name).collect(),
+        false => profiles.iter().collect(),
+    };
+    if names.is_empty() {
+        return Some(vec![(None, config.clone())]);
+    }
+
+    let mut targets = Vec::new();
+    for name in names {
+        let Some(profile) = config.with_profile(name) else {
+            eprintln!("Profile '{0}' not found in pipeline", name);
+            return None;
+        };
+        targets.push((Some(name.clone()), profile));
+    }
+    return Some(targets);
+}
 
-    // Load and parse configuration
-    let config_contents = read_to_string(config_file).unwrap();
-    let config_result = json::parse(config_contents.as_str());
-    if config_result.is_err() {
-        // Pull error from unwrap and safely exit
+/// Returns the output directory given on the command line for a profile, which outputs into a folder of its own.
+pub fn profile_output(output: Option<&String>, profile: Option<&str>) -> Option<String> {
+    return output.map(|output| match profile {
+        Some(name) => Path::new(output).join(name).to_str().unwrap().to_string(),
+        None => output.clone(),
+    });
+}
+
+/// Loads an IronPress Pipeline JSON file and builds it, once for each of the requested profiles.
+/// Returns TRUE if every map of every profile was converted, FALSE otherwise.
+pub fn from_file(config_file: &Path, options: &BuildOptions) -> bool {
+    let Some(config) = load_config(config_file) else {
+        return false;
+    };
+    if !verbosity::is_quiet() {
         println!(
-            "While parsing JSON config, got error:\t{0}",
-            config_result.unwrap_err()
+            "Successfully loaded config at {0}",
+            config_file.to_str().unwrap()
         );
-        return;
     }
-    let config = config_result.unwrap();
+    return from_config(config_file, config, options);
+}
+
+/// Builds a pipeline that was already loaded from `config_file`, once for each of the requested profiles.
+/// Returns TRUE if every map of every profile was converted, FALSE otherwise.
+pub fn from_config(config_file: &Path, config: PipelineConfig, options: &BuildOptions) -> bool {
+    // Check that every requested material exists
+    for name in options.materials.iter() {
+        if !config.materials.iter().any(|mat| mat.name.eq(name)) {
+            eprintln!("Material '{0}' not found in pipeline", name);
+            return false;
+        }
+    }
+
+    let Some(targets) = targets(&config, &options.profiles, options.all_profiles) else {
+        return false;
+    };
+
+    let mut success = true;
+    for (profile, config) in targets {
+        if let Some(name) = profile.as_ref().filter(|_| !verbosity::is_quiet()) {
+            println!("Building profile '{0}'", name);
+        }
+        success &= build(config_file, config, profile.as_deref(), options);
+    }
+    return success;
+}
+
+/// Converts each map of the materials of a pipeline on a pool of worker threads, awaiting until all maps are completed.
+/// At most `options.jobs` maps are converted at once, and maps are held back while their estimated memory would exceed `options.memory_budget`.
+/// Maps whose sources and settings are unchanged since they were last built are skipped, unless `options.force` is set.
+/// Returns TRUE if every map was converted, FALSE otherwise.
+fn build(config_file: &Path, mut config: PipelineConfig, profile: Option<&str>, options: &BuildOptions) -> bool {
+    let dir = config_file.parent().unwrap(); // Get working directory
+    let dryrun = options.dryrun;
 
     // Get output directory, relative to parent (or replacing it, if path is absolute)
-    let outdir_buf = dir.join(Path::new(&(config["output"].as_str().unwrap())));
+    let output = profile_output(options.output.as_ref(), profile);
+    let outdir_buf = output_dir(config_file, &config, output.as_ref());
     let outdir = outdir_buf.as_path();
     if !outdir.exists() && !dryrun {
         // If path does not exist, create all folders so it does
-        fs::create_dir_all(outdir).unwrap();
+        if let Err(err) = fs::create_dir_all(outdir) {
+            eprintln!("Failed to create output directory {0}, {1}", outdir.to_str().unwrap(), err);
+            return false;
+        }
     }
 
     // Get input directory, relative to parent (or replacing it, if path is absolute)
-    let indir_buf = dir.join(Path::new(&(config["input"].as_str().unwrap())));
+    let indir_buf = dir.join(Path::new(&config.input));
     let indir = indir_buf.as_path();
     if !indir.exists() {
         // If path does not exist, create all folders so it does
-        fs::create_dir_all(indir).unwrap();
+        if let Err(err) = fs::create_dir_all(indir) {
+            eprintln!("Failed to create input directory {0}, {1}", indir.to_str().unwrap(), err);
+            return false;
+        }
     }
+    // Sources are looked up many times per map, so the directory is only listed once
+    let source_dir = Arc::new(SourceDir::read(indir));
+
+    // Check sources are unambiguous and were authored at their declared resolution, and GPU memory budgets, which may reduce the size of materials
+    let mut diagnostics = Diagnostics::default();
+    for mat in config.materials.iter() {
+        if options.materials.is_empty() || options.materials.contains(&mat.name) {
+            validate::check_ambiguity(&source_dir, mat, &mut diagnostics);
+            validate::check_resolution(&source_dir, mat, &mut diagnostics);
+        }
+    }
+    budget::enforce(&mut config, &source_dir, &mut diagnostics);
+    diagnostics.print();
 
-    println!(
-        "Successfully loaded config at {0}",
-        config_file.to_str().unwrap()
-    );
-
-    let mats = config["materials"].entries();
+    let jobs = match options.jobs {
+        Some(jobs) => jobs as usize,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
 
-    // Check if we're using DirectX normals--if true, flip green channels
-    let flip_normals = config.has_key("flip_normals") && config["flip_normals"].as_bool().unwrap();
+    // Record of what each output was last built from
+    let mut manifest = Manifest::load(outdir);
 
     let time_start = time::Instant::now();
     let mut num_materials: u32 = 0;
     let mut num_maps: u32 = 0;
-
-    let mut threads: Vec<JoinHandle<()>> = Vec::new();
-
-    // Iterate through all materials
-    for (material_name, mat) in mats {
-        let channels = mat["channels"].clone();
-        let res = mat["max_dimension"].as_u32().unwrap();
-        let has_alpha = mat.has_key("alpha");
+    let mut num_skipped: u32 = 0;
+    // Maps that failed to convert, by their index in the queue
+    let mut failures: Vec<(usize, Error)> = Vec::new();
+
+    let mut queued: Vec<(MapJob, u64)> = Vec::new();
+    let mut pending: Vec<PendingMap> = Vec::new();
+    let mut reports: Vec<MapReport> = Vec::new();
+
+    // Iterate through all materials, or only the requested ones
+    let materials = config
+        .materials
+        .iter()
+        .filter(|mat| options.materials.is_empty() || options.materials.contains(&mat.name));
+    for mat in materials {
         num_materials += 1;
 
-        for member in channels.members() {
-            let mem = member.as_str().unwrap();
+        for channel in mat.channels.iter() {
             num_maps += 1;
 
-            // Spawn thread with basic map conversion information
-            threads.push(threaded_convert(
-                indir_buf.to_str().unwrap().to_string(),
-                outdir_buf.to_str().unwrap().to_string(),
-                material_name.to_string(),
-                mem.to_string(),
-                res,
-                flip_normals,
-                has_alpha,
+            let job = MapJob {
+                input_dir: Arc::clone(&source_dir),
+                output_dir: outdir_buf.clone(),
+                material: mat.name.clone(),
+                channel: channel.name.clone(),
+                resolution: mat.max_dimension,
+                flip_green: mat.flip_normals, // If we're using DirectX normals, flip green channels
+                renormalize: mat.renormalize_normals,
+                normal_source: mat.normal_source,
+                has_alpha: mat.alpha,
+                layout: channel.layout.clone(),
+                unpack: mat.unpack.clone(),
+                format: mat.format,
+                naming: mat.naming.clone(),
+                color_spaces: mat.color_spaces.clone(),
+                mip_filter: channel.mip_filter,
                 dryrun,
-            ));
+            };
+
+            // Skip maps that are already up to date
+            let out_path = mat.naming.output_path(outdir, &mat.name, &channel.name, mat.format.extension());
+            let output = out_path.file_name().unwrap().to_str().unwrap().to_string();
+            let key = job.input_hash();
+
+            let sources = job.sources();
+            let mut color_type = output_color(&channel.name, channel.layout.as_ref(), mat.alpha);
+            if channel.name.eq("normal") {
+                color_type = mat.format.normals.color_type();
+            }
+            let mut map_report = MapReport {
+                material: mat.name.clone(),
+                channel: channel.name.clone(),
+                output: out_path.clone(),
+                input_bytes: sources.iter().map(|path| report::file_size(path)).sum(),
+                sources,
+                status: Status::Skipped,
+                dimensions: None,
+                color_type,
+                output_bytes: 0,
+                gpu_bytes: None,
+                millis: None,
+            };
+            let pixel_format = budget::channel_format(mat, channel);
+
+            if !options.force && outputs_exist(&out_path, &mat.format) && manifest.is_current(&output, &key) {
+                num_skipped += 1;
+                if !verbosity::is_quiet() {
+                    println!("\tSkipped {0} (up to date)", out_path.to_str().unwrap());
+                }
+                map_report.dimensions = inspect::dimensions(&out_path);
+                map_report.output_bytes = report::output_size(&out_path);
+                map_report.gpu_bytes = map_report
+                    .dimensions
+                    .map(|(width, height)| budget::texture_bytes(width, height, pixel_format, mat.format.mips()));
+                reports.push(map_report);
+                continue;
+            }
+
+            // Only estimate memory use if there's a budget, as it reads the header of every source
+            let cost = match options.memory_budget {
+                Some(_) => job.memory_estimate(),
+                None => 0,
+            };
+            queued.push((job, cost));
+            pending.push(PendingMap {
+                output,
+                key,
+                report: reports.len(),
+                pixel_format,
+                mips: mat.format.mips(),
+            });
+            reports.push(map_report);
         }
     }
 
-    // Wait on all threads
-    for t in threads {
-        t.join().expect("Await thread to rejoin main thread");
+    // Convert all queued maps, and record whether each was built in the manifest
+    let budget = options.memory_budget.map(|mb| mb * 1024 * 1024);
+    pool::run(queued, jobs, budget, convert, |index, converted| {
+        let map = &pending[index];
+        let map_report = &mut reports[map.report];
+        match converted.unwrap_or_else(|message| Err(Error::Panic(message))) {
+            Ok(converted) => {
+                manifest.insert(&map.output, &map.key);
+                map_report.status = if dryrun { Status::Processed } else { Status::Built };
+                map_report.dimensions = Some((converted.width, converted.height));
+                map_report.gpu_bytes = Some(budget::texture_bytes(
+                    converted.width,
+                    converted.height,
+                    map.pixel_format,
+                    map.mips,
+                ));
+                map_report.millis = Some(converted.millis);
+                if !dryrun {
+                    map_report.output_bytes = report::output_size(&map_report.output);
+                }
+            }
+            Err(err) => {
+                eprintln!("\tFailed {0}", map_report.output.to_str().unwrap());
+                manifest.remove(&map.output);
+                map_report.status = Status::Failed(err.to_string());
+                failures.push((index, err));
+            }
+        }
+    });
+    failures.sort_by_key(|(index, _)| *index);
+
+    if !dryrun {
+        if let Err(err) = manifest.save(outdir) {
+            eprintln!("Failed to write build manifest, {0}", err);
+        }
     }
 
     let time_end = time::Instant::now();
-    println!(
-        "Completed {0} materials with {1} exported maps, in {2} ms",
-        num_materials,
-        num_maps,
-        (time_end - time_start).as_millis()
-    );
+    let num_failed = failures.len() as u32;
+    if num_failed > 0 {
+        eprintln!("{0} of {1} maps failed:", num_failed, num_maps);
+        for (index, err) in failures.iter() {
+            eprintln!("\t{0}: {1}", reports[pending[*index].report].output.to_str().unwrap(), err);
+        }
+    }
+    if !verbosity::is_quiet() {
+        println!(
+            "Completed {0} materials with {1} exported maps and {2} up to date, in {3} ms",
+            num_materials,
+            num_maps - num_skipped - num_failed,
+            num_skipped,
+            (time_end - time_start).as_millis()
+        );
+    }
+
+    // Write report of every map, relative to the working directory
+    if let Some(path) = options.report.as_ref() {
+        let path = report::path(Path::new(path), profile);
+        if let Err(err) = report::write(&path, config_file, profile, &reports, (time_end - time_start).as_millis()) {
+            eprintln!("Failed to write build report to {0}, {1}", path.to_str().unwrap(), err);
+            return false;
+        }
+    }
+    return num_failed == 0;
 }
 
 #[cfg(test)]
@@ -222,6 +668,92 @@ mod tests {
     use std::{env, fs, path::PathBuf};
 
     use super::from_file;
+    use crate::cli::args::BuildOptions;
+    use crate::util::test_dir;
+
+    #[test]
+    fn continue_after_failures() {
+        let dir = test_dir("continue_after_failures", &[]);
+        fs::create_dir_all(dir.join("in")).expect("Create test input directory");
+        image::RgbImage::new(4, 4)
+            .save(dir.join("in/mat_a_basecolor.png"))
+            .expect("Write test source");
+        fs::write(dir.join("in/mat_a_roughness.png"), "not a png").expect("Write corrupt test source");
+        let path_pipeline = dir.join("pipeline.json");
+        fs::write(
+            &path_pipeline,
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "materials": {
+                    "mat_a": { "max_dimension": 4, "channels": ["roughness", "normal", "basecolor"] }
+                }
+            }"#,
+        )
+        .expect("Write test pipeline");
+
+        // Failed maps don't stop the others from being built, but fail the build
+        assert!(!from_file(&path_pipeline, &BuildOptions::default()));
+        assert!(dir.join("out/mat_a_basecolor.png").exists());
+        assert!(!dir.join("out/mat_a_roughness.png").exists());
+        assert!(!dir.join("out/mat_a_normal.png").exists());
+    }
+
+    #[test]
+    fn rebuild_missing_mips() {
+        let dir = test_dir("rebuild_missing_mips", &[]);
+        fs::create_dir_all(dir.join("in")).expect("Create test input directory");
+        image::RgbImage::new(4, 4)
+            .save(dir.join("in/mat_a_basecolor.png"))
+            .expect("Write test source");
+        let path_pipeline = dir.join("pipeline.json");
+        fs::write(
+            &path_pipeline,
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "format": { "container": "png", "mips": true },
+                "materials": {
+                    "mat_a": { "max_dimension": 4, "channels": ["basecolor"] }
+                }
+            }"#,
+        )
+        .expect("Write test pipeline");
+
+        // A deleted mip level is rebuilt, even though the base level is up to date
+        assert!(from_file(&path_pipeline, &BuildOptions::default()));
+        let mip = dir.join("out/mat_a_basecolor_mip2.png");
+        assert!(mip.exists());
+        fs::remove_file(&mip).expect("Delete test mip level");
+        assert!(from_file(&path_pipeline, &BuildOptions::default()));
+        assert!(mip.exists());
+    }
+
+    #[test]
+    fn keep_normals() {
+        let dir = test_dir("keep_normals", &[]);
+        fs::create_dir_all(dir.join("in")).expect("Create test input directory");
+        // Non-unit vectors, which are only renormalized on request
+        let normals = image::ImageBuffer::<image::Rgb<u16>, Vec<u16>>::from_pixel(4, 4, image::Rgb([40000, 32768, 50000]));
+        normals.save(dir.join("in/mat_a_normal.png")).expect("Write test source");
+        let path_pipeline = dir.join("pipeline.json");
+        fs::write(
+            &path_pipeline,
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "renormalize_normals": false,
+                "materials": {
+                    "mat_a": { "max_dimension": 4, "channels": ["normal"] }
+                }
+            }"#,
+        )
+        .expect("Write test pipeline");
+
+        assert!(from_file(&path_pipeline, &BuildOptions::default()));
+        let output = image::open(dir.join("out/mat_a_normal.png")).expect("Read test output");
+        assert_eq!(output.into_rgb16(), normals);
+    }
 
     #[test]
     fn validate_pipeline() {
@@ -229,8 +761,8 @@ mod tests {
         let path_directory = env::current_dir().expect("Get current working directory");
         let path_pipeline = path_directory.join(PathBuf::from("test/clover/texture_pipeline.json"));
 
-        // Find output directory
-        let path_output = path_directory.join(PathBuf::from("test/clover/out"));
+        // Build into a temporary output directory, so the repository tree is left untouched
+        let path_output = env::temp_dir().join("ironpress_validate_pipeline");
         // If we had an existing output directory, destroy it to ensure we can create them on the fly
         if path_output.exists() {
             fs::remove_dir_all(path_output.clone())
@@ -244,30 +776,37 @@ mod tests {
         assert!(path_pipeline.exists(), "Test pipeline file didn't exist"); // Ensure that the filepath exists
 
         // Perform our pipeline output
-        from_file(&path_pipeline, false);
+        let options = BuildOptions {
+            output: Some(path_output.to_str().unwrap().to_string()),
+            ..BuildOptions::default()
+        };
+        from_file(&path_pipeline, &options);
 
         // Ensure that our output directory exists
         assert!(path_output.exists(), "Output directory didn't exist");
 
         // For each output file...
-        let textures = vec![
-            path_directory.join(PathBuf::from("test/clover/out/mat_fur_mask.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_daisy_basecolor.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_daisy_arm.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_daisy_normal.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_body_basecolor.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_body_arm.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_body_normal.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_sunhat_basecolor.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_sunhat_arm.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_sunhat_normal.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_sunhat_mask.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_shears_basecolor.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_shears_arm.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_shears_normal.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_eye_basecolor.png")),
-            path_directory.join(PathBuf::from("test/clover/out/mat_eye_normal.png")),
-        ];
+        let textures: Vec<PathBuf> = [
+            "mat_fur_mask.png",
+            "mat_daisy_basecolor.png",
+            "mat_daisy_arm.png",
+            "mat_daisy_normal.png",
+            "mat_body_basecolor.png",
+            "mat_body_arm.png",
+            "mat_body_normal.png",
+            "mat_sunhat_basecolor.png",
+            "mat_sunhat_arm.png",
+            "mat_sunhat_normal.png",
+            "mat_sunhat_mask.png",
+            "mat_shears_basecolor.png",
+            "mat_shears_arm.png",
+            "mat_shears_normal.png",
+            "mat_eye_basecolor.png",
+            "mat_eye_normal.png",
+        ]
+        .iter()
+        .map(|name| path_output.join(name))
+        .collect();
 
         // ...verify that it outputted...
         for path_texture in textures.iter() {
@@ -0,0 +1,122 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+/// Jobs waiting to be run, and the estimated memory of the jobs currently running.
+struct Queue<J> {
+    jobs: VecDeque<(usize, J, u64)>,
+    in_use: u64,
+}
+
+/// Returns true if a job of the given cost can start while `in_use` bytes are taken.
+/// A job that exceeds the budget on its own is still run, once nothing else is running.
+fn fits(cost: u64, in_use: u64, budget: Option<u64>) -> bool {
+    return match budget {
+        Some(budget) => in_use == 0 || in_use + cost <= budget,
+        None => true,
+    };
+}
+
+/// Returns the message of a panic, if it has one.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    return payload.downcast_ref::<String>().cloned().unwrap_or_else(|| "unknown panic".to_string());
+}
+
+/// Runs `work` on every job with a fixed number of worker threads, taking jobs in order.
+/// Each job comes with an estimate of the memory it uses in bytes, and jobs are held back while starting them would exceed `budget`.
+/// `finish` is called on the calling thread with the index of each job and its result as jobs complete,
+/// or the panic message if the job panicked.
+pub fn run<J, R>(
+    jobs: Vec<(J, u64)>,
+    workers: usize,
+    budget: Option<u64>,
+    work: impl Fn(J) -> R + Sync,
+    mut finish: impl FnMut(usize, Result<R, String>),
+) where
+    J: Send,
+    R: Send,
+{
+    let num_jobs = jobs.len();
+    let queue = Mutex::new(Queue {
+        jobs: jobs.into_iter().enumerate().map(|(i, (job, cost))| (i, job, cost)).collect(),
+        in_use: 0,
+    });
+    let changed = Condvar::new();
+    let (sender, receiver) = mpsc::channel::<(usize, Result<R, String>)>();
+
+    thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, num_jobs.max(1)) {
+            let sender = sender.clone();
+            let (queue, changed, work) = (&queue, &changed, &work);
+            scope.spawn(move || loop {
+                // Wait until the next job fits in the memory budget, so large jobs aren't starved by smaller ones
+                let mut state = queue.lock().unwrap();
+                let (index, job, cost) = loop {
+                    match state.jobs.front() {
+                        None => return,
+                        Some((_, _, cost)) if fits(*cost, state.in_use, budget) => break state.jobs.pop_front().unwrap(),
+                        Some(_) => state = changed.wait(state).unwrap(),
+                    }
+                };
+                state.in_use += cost;
+                drop(state);
+
+                let result = panic::catch_unwind(AssertUnwindSafe(|| work(job))).map_err(panic_message);
+
+                queue.lock().unwrap().in_use -= cost;
+                changed.notify_all();
+                if sender.send((index, result)).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(sender);
+
+        for (index, result) in receiver.iter() {
+            finish(index, result);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn bounded_memory() {
+        let in_use = AtomicU64::new(0);
+        let peak = AtomicU64::new(0);
+        let jobs: Vec<(u64, u64)> = [40, 30, 30, 20, 90, 10, 50].iter().map(|cost| (*cost, *cost)).collect();
+
+        let mut results: Vec<Result<u64, String>> = vec![Err(String::new()); jobs.len()];
+        run(
+            jobs,
+            4,
+            Some(60),
+            |cost| {
+                let current = in_use.fetch_add(cost, Ordering::SeqCst) + cost;
+                peak.fetch_max(current, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(10));
+                in_use.fetch_sub(cost, Ordering::SeqCst);
+                if cost == 10 {
+                    panic!("Job failed");
+                }
+                cost * 2
+            },
+            |index, result| results[index] = result,
+        );
+
+        // The 90 byte job exceeds the budget, so it runs alone
+        assert_eq!(peak.load(Ordering::SeqCst), 90);
+        assert_eq!(results, vec![Ok(80), Ok(60), Ok(60), Ok(40), Ok(180), Err("Job failed".to_string()), Ok(100)]);
+    }
+}
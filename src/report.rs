@@ -0,0 +1,186 @@
+use crate::format::output;
+use image::ColorType;
+use json::JsonValue;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Outcome of a single map in a build.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    /// The map was converted and written out.
+    Built,
+    /// The map was converted, but not written out, as this was a dry run.
+    Processed,
+    /// The map was up to date, and not converted.
+    Skipped,
+    /// The map failed to convert, for the given reason.
+    Failed(String),
+}
+
+impl Status {
+    /// Returns the name of the status, as written in the report.
+    fn name(&self) -> &'static str {
+        return match self {
+            Status::Built => "built",
+            Status::Processed => "processed",
+            Status::Skipped => "skipped",
+            Status::Failed(_) => "failed",
+        };
+    }
+}
+
+/// Statistics of a single output texture.
+#[derive(Debug, Clone)]
+pub struct MapReport {
+    pub material: String,
+    pub channel: String,
+    /// Path of the output texture.
+    pub output: PathBuf,
+    /// Paths of the source textures the map is built from.
+    pub sources: Vec<PathBuf>,
+    pub status: Status,
+    /// Dimensions of the base level of the output texture, if known.
+    pub dimensions: Option<(u32, u32)>,
+    /// Color format of the output texture.
+    pub color_type: ColorType,
+    /// Total size of the source textures, in bytes.
+    pub input_bytes: u64,
+    /// Total size of the output texture, including any mip levels written as their own files, in bytes.
+    pub output_bytes: u64,
+    /// Estimated GPU memory of the output texture and its mip levels, in bytes, if its dimensions are known.
+    pub gpu_bytes: Option<u64>,
+    /// Time taken to convert the map, if it was converted.
+    pub millis: Option<u128>,
+}
+
+/// Returns the size of a file in bytes, or 0 if it does not exist.
+pub fn file_size(path: &Path) -> u64 {
+    return fs::metadata(path).map_or(0, |metadata| metadata.len());
+}
+
+/// Returns the total size of an output texture in bytes, including any mip levels written as their own files.
+pub fn output_size(path: &Path) -> u64 {
+    let mut size = file_size(path);
+    let mut level = 1;
+    while output::mip_path(path, level).exists() {
+        size += file_size(&output::mip_path(path, level));
+        level += 1;
+    }
+    return size;
+}
+
+impl MapReport {
+    /// Returns the report of this map as JSON.
+    fn to_json(&self) -> JsonValue {
+        let mut value = json::object! {
+            material: self.material.as_str(),
+            channel: self.channel.as_str(),
+            output: self.output.to_str().unwrap(),
+            sources: self.sources.iter().map(|path| path.to_str().unwrap()).collect::<Vec<&str>>(),
+            status: self.status.name(),
+            width: self.dimensions.map(|(width, _)| width),
+            height: self.dimensions.map(|(_, height)| height),
+            color_type: format!("{0:?}", self.color_type),
+            input_bytes: self.input_bytes,
+            output_bytes: self.output_bytes,
+            gpu_bytes: self.gpu_bytes,
+            time_ms: self.millis.map(|millis| millis as u64),
+        };
+        if let Status::Failed(reason) = &self.status {
+            value["error"] = reason.as_str().into();
+        }
+        return value;
+    }
+}
+
+/// Returns the path to write the report of a build to. Builds of a profile suffix the file stem with the name of the profile.
+pub fn path(path: &Path, profile: Option<&str>) -> PathBuf {
+    let Some(profile) = profile else {
+        return path.to_path_buf();
+    };
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("report");
+    return match path.extension().and_then(|ext| ext.to_str()) {
+        Some(extension) => path.with_file_name(format!("{0}_{1}.{2}", stem, profile, extension)),
+        None => path.with_file_name(format!("{0}_{1}", stem, profile)),
+    };
+}
+
+/// Returns a JSON report of a build, with totals across every map.
+pub fn to_json(config_file: &Path, profile: Option<&str>, maps: &[MapReport], millis: u128) -> JsonValue {
+    let count = |status: &str| maps.iter().filter(|map| map.status.name() == status).count();
+    return json::object! {
+        version: env!("CARGO_PKG_VERSION"),
+        pipeline: config_file.to_str().unwrap(),
+        profile: profile,
+        time_ms: millis as u64,
+        totals: json::object! {
+            maps: maps.len(),
+            built: count("built") + count("processed"),
+            skipped: count("skipped"),
+            failed: count("failed"),
+            input_bytes: maps.iter().map(|map| map.input_bytes).sum::<u64>(),
+            output_bytes: maps.iter().map(|map| map.output_bytes).sum::<u64>(),
+            gpu_bytes: maps.iter().filter_map(|map| map.gpu_bytes).sum::<u64>(),
+        },
+        outputs: maps.iter().map(|map| map.to_json()).collect::<Vec<JsonValue>>(),
+    };
+}
+
+/// Writes a JSON report of a build to the given path.
+pub fn write(path: &Path, config_file: &Path, profile: Option<&str>, maps: &[MapReport], millis: u128) -> std::io::Result<()> {
+    return fs::write(path, json::stringify_pretty(to_json(config_file, profile, maps, millis), 4));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{path, to_json, MapReport, Status};
+    use image::ColorType;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn report_totals() {
+        let built = MapReport {
+            material: "mat_a".to_string(),
+            channel: "basecolor".to_string(),
+            output: PathBuf::from("out/mat_a_basecolor.png"),
+            sources: vec![PathBuf::from("in/mat_a_basecolor.png")],
+            status: Status::Built,
+            dimensions: Some((64, 32)),
+            color_type: ColorType::Rgb8,
+            input_bytes: 1000,
+            output_bytes: 200,
+            gpu_bytes: Some(8192),
+            millis: Some(12),
+        };
+        let failed = MapReport {
+            channel: "normal".to_string(),
+            output: PathBuf::from("out/mat_a_normal.png"),
+            sources: Vec::new(),
+            status: Status::Failed("source map not found".to_string()),
+            dimensions: None,
+            color_type: ColorType::Rgb16,
+            input_bytes: 0,
+            output_bytes: 0,
+            gpu_bytes: None,
+            millis: None,
+            ..built.clone()
+        };
+
+        let report = to_json(Path::new("pipeline.json"), Some("mobile"), &[built, failed], 20);
+        assert_eq!(report["profile"], "mobile");
+        assert_eq!(report["totals"]["maps"], 2);
+        assert_eq!(report["totals"]["built"], 1);
+        assert_eq!(report["totals"]["failed"], 1);
+        assert_eq!(report["totals"]["input_bytes"], 1000);
+        assert_eq!(report["totals"]["gpu_bytes"], 8192);
+        assert_eq!(report["outputs"][0]["width"], 64);
+        assert_eq!(report["outputs"][0]["sources"][0], "in/mat_a_basecolor.png");
+        assert_eq!(report["outputs"][0]["time_ms"], 12);
+        assert_eq!(report["outputs"][1]["status"], "failed");
+        assert_eq!(report["outputs"][1]["error"], "source map not found");
+        assert!(report["outputs"][1]["width"].is_null());
+
+        assert_eq!(path(Path::new("out/report.json"), Some("mobile")), PathBuf::from("out/report_mobile.json"));
+        assert_eq!(path(Path::new("out/report.json"), None), PathBuf::from("out/report.json"));
+    }
+}
@@ -1,33 +1,51 @@
-use image::codecs::png;
-use image::{
-    imageops, ColorType, DynamicImage, ExtendedColorType, GenericImageView, ImageBuffer, ImageEncoder, Rgb
-};
+use crate::error::{Error, Result};
+use image::{imageops, ColorType, DynamicImage, GenericImageView, ImageBuffer, Rgb, Rgba32FImage};
 use std::cmp::max;
-use std::fs::File;
-use std::io::BufWriter;
-use std::path::{Path, PathBuf};
+use std::path::Path;
+#[cfg(test)]
+use std::{env, fs, path::PathBuf};
 
 // pub type Gray16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
 // pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
 pub type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
 
+/// Loads an image from the given filepath.
+pub fn load_image(path: &Path) -> Result<DynamicImage> {
+    return image::open(path).map_err(|source| Error::Load { path: path.to_path_buf(), source });
+}
+
 /// Loads an image from the given filepath, converting it to the specified color format.
+/// If `srgb` is set, the image is resized in linear light.
+/// Images are resized at the precision they were decoded at, and only converted once resized.
+/// Float images, such as EXR, are treated as linear, and encoded as sRGB if `srgb` is set.
 pub fn load_image_adv(
     path: &Path,
     res: u32,
     convert_to: ColorType,
-) -> (DynamicImage, u32, u32) {
-    let img_result = image::open(path);
-    if img_result.as_ref().is_err() {
-        eprintln!(
-            "Failed to open image at path {0}, got error {1}",
-            path.to_str().unwrap(),
-            img_result.as_ref().unwrap_err().to_string()
-        );
+    srgb: bool,
+) -> Result<(DynamicImage, u32, u32)> {
+    let img = load_image(path)?;
+
+    // Float sources, such as EXR, hold linear values, so they're resized at full precision and only then encoded
+    if matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F) {
+        let (resized, width, height) = auto_resize(img, res, res);
+        let mut linear = resized.into_rgba32f();
+        if srgb {
+            image_to_srgb(&mut linear);
+        }
+        return Ok((convert_color(DynamicImage::from(linear), convert_to), width, height));
     }
-    let img = img_result.unwrap();
 
-    let map = match convert_to {
+    let (resized, width, height) = match srgb {
+        true => auto_resize_srgb(DynamicImage::from(img.into_rgba32f()), res, res),
+        false => auto_resize(img, res, res),
+    };
+    return Ok((convert_color(resized, convert_to), width, height));
+}
+
+/// Converts an image to the specified color format.
+pub fn convert_color(img: DynamicImage, convert_to: ColorType) -> DynamicImage {
+    return match convert_to {
         ColorType::Rgb8 => DynamicImage::from(img.into_rgb8()),
         ColorType::Rgb16 => DynamicImage::from(img.into_rgb16()),
         ColorType::L8 => DynamicImage::from(img.into_luma8()),
@@ -38,57 +56,108 @@ pub fn load_image_adv(
         ColorType::Rgba32F => DynamicImage::from(img.into_rgba32f()),
         _ => DynamicImage::from(img.into_rgb8()),
     };
-
-    return auto_resize(map, res, res);
 }
 
-/// Returns the color format for the given map name.
-pub fn map_to_color(map_name: &str) -> ColorType {
+/// Returns the color format of a map name, if it is known.
+fn known_color(map_name: &str) -> Option<ColorType> {
     return match map_name {
-        "basecolor" => ColorType::Rgb8,
-        "diffuse" => ColorType::Rgb8,
+        "basecolor" => Some(ColorType::Rgb8),
+        "diffuse" => Some(ColorType::Rgb8),
 
-        "basecoloralpha" => ColorType::Rgba8, // If we're including alpha in our basecolor, forcibly include it
+        "basecoloralpha" => Some(ColorType::Rgba8), // If we're including alpha in our basecolor, forcibly include it
 
-        "normal" => ColorType::Rgb16,
+        "normal" => Some(ColorType::Rgb16),
 
-        "ao" => ColorType::L8,
-        "occlusion" => ColorType::L8,
-        "roughness" => ColorType::L8,
-        "metallic" => ColorType::L8,
-        "metalness" => ColorType::L8,
+        "ao" => Some(ColorType::L8),
+        "occlusion" => Some(ColorType::L8),
+        "roughness" => Some(ColorType::L8),
+        "metallic" => Some(ColorType::L8),
+        "metalness" => Some(ColorType::L8),
 
-        "mask" => ColorType::L8,
-        "opacity" => ColorType::L8,
-        "alpha" => ColorType::L8,
+        "mask" => Some(ColorType::L8),
+        "opacity" => Some(ColorType::L8),
+        "alpha" => Some(ColorType::L8),
 
-        _ => ColorType::Rgb8,
+        _ => None,
     };
 }
 
+/// Returns the color format for the given map name, which is 8-bit RGB for unknown maps.
+pub fn map_to_color(map_name: &str) -> ColorType {
+    return known_color(map_name).unwrap_or(ColorType::Rgb8);
+}
+
+/// Returns true if the given map name has a known color format in [`map_to_color`], rather than falling back to 8-bit RGB.
+pub fn map_is_known(map_name: &str) -> bool {
+    return known_color(map_name).is_some();
+}
+
+/// Returns true if the given map stores sRGB encoded color, rather than linear data.
+pub fn map_is_srgb(map_name: &str) -> bool {
+    return matches!(map_name, "basecolor" | "diffuse" | "basecoloralpha");
+}
+
+/// Converts an sRGB encoded value in the range [0, 1] to linear light.
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        return v / 12.92;
+    }
+    return ((v + 0.055) / 1.055).powf(2.4);
+}
+
+/// Converts a linear light value in the range [0, 1] to sRGB encoding.
+pub fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        return v * 12.92;
+    }
+    return 1.055 * v.powf(1.0 / 2.4) - 0.055;
+}
+
+/// Converts the RGB channels of an image from sRGB to linear, in place.
+pub fn image_to_linear(img: &mut Rgba32FImage) {
+    for pixel in img.pixels_mut() {
+        for c in 0..3 {
+            pixel.0[c] = srgb_to_linear(pixel.0[c]);
+        }
+    }
+}
+
+/// Converts the RGB channels of an image from linear to sRGB, in place.
+pub fn image_to_srgb(img: &mut Rgba32FImage) {
+    for pixel in img.pixels_mut() {
+        for c in 0..3 {
+            pixel.0[c] = linear_to_srgb(pixel.0[c]);
+        }
+    }
+}
+
 // pub fn int16_to_float64(a: u16) -> f64 {
 //     return (a as f64) / (u16::MAX as f64);
 // }
 
+/// Returns the dimensions that [`auto_resize`] resizes an image of the given dimensions to.
+pub fn fit_dimensions(dim_x: u32, dim_y: u32, width: u32, height: u32) -> (u32, u32) {
+    // If our image dimensions are not equal, find the ratio between the max values, and scale accordingly
+    if dim_x != dim_y {
+        let scale_factor = max(width, height) as f64 / max(dim_x, dim_y) as f64;
+        return ((dim_x as f64 * scale_factor) as u32, (dim_y as f64 * scale_factor) as u32);
+    }
+    return (width, height);
+}
+
 /// Automatically resizes an image, preserving aspect ratio, so the maximum dimension of the image matches the max specified dimension.
 /// If the image already matches the specified dimension, then no operation is performed.
 pub fn auto_resize(
     img: DynamicImage,
-    mut width: u32,
-    mut height: u32,
+    width: u32,
+    height: u32,
 ) -> (DynamicImage, u32, u32) {
     let (dim_x, dim_y) = img.dimensions();
+    let (width, height) = fit_dimensions(dim_x, dim_y, width, height);
 
     // Pick resizing filter based off of what we're doing, up-scaling versus downscaling
     // https://stackoverflow.com/questions/384991/what-is-the-best-image-downscaling-algorithm-quality-wise
 
-    // If our image dimensions are not equal, find the ratio between the max values, and scale accordingly
-    if dim_x != dim_y {
-        let scale_factor = max(width, height) as f64 / max(dim_x, dim_y) as f64;
-        width = (dim_x as f64 * scale_factor) as u32;
-        height = (dim_y as f64 * scale_factor) as u32;
-    }
-
     if dim_x > width || dim_y > height {
         // If we're up-scaling an image, use Catmull Rom
         return (
@@ -108,41 +177,81 @@ pub fn auto_resize(
     return (img, width, height);
 }
 
-/// Saves an image buffer to the given path, using a specific color format, at the best compression
-pub fn compressed_save(
-    path: &Path,
-    buffer: &[u8],
-    width: u32,
-    height: u32,
-    format: ExtendedColorType,
-) {
-    let f = File::create(path).unwrap(); // Create a file at the given path
-    let writer = BufWriter::new(f); // Create a writer buffer to it
-
-    // Set up a PNG encoder on top of the buffer, and attempt to maximize compression (for space efficiency)
-    let encoder = png::PngEncoder::new_with_quality(
-        writer,
-        png::CompressionType::Best,
-        png::FilterType::Adaptive,
-    );
-
-    encoder.write_image(buffer, width, height, format).unwrap(); // Finally, write out the image
-}
-
-/// Creates a texture filepath for the given parameters.
-/// Can be absolute or relative, depending on `directory` input.
-pub fn path_material_map(
-    directory: &Path,
-    material_name: &str,
-    data: &str,
-    format: &str,
-) -> PathBuf {
-    // Concatenate strings together
-    let mut owned_str: String = material_name.to_string();
-    owned_str.push_str("_");
-    owned_str.push_str(data);
-    owned_str.push_str(".");
-    owned_str.push_str(format);
-
-    return directory.join(Path::new(owned_str.as_str()));
+/// Resizes an sRGB encoded image as [`auto_resize`] does, but resamples it in linear light so contrast is preserved.
+/// The image keeps its color format.
+pub fn auto_resize_srgb(img: DynamicImage, width: u32, height: u32) -> (DynamicImage, u32, u32) {
+    let ct = img.color();
+    let mut linear = img.into_rgba32f();
+    image_to_linear(&mut linear);
+
+    let (resized, width, height) = auto_resize(DynamicImage::from(linear), width, height);
+    let mut encoded = resized.into_rgba32f();
+    image_to_srgb(&mut encoded);
+    return (convert_color(DynamicImage::from(encoded), ct), width, height);
+}
+
+/// Returns an empty temporary directory for a test, containing only the given empty files.
+/// Anything left over from earlier runs is removed first.
+#[cfg(test)]
+pub fn test_dir(name: &str, files: &[&str]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ironpress_{0}", name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Create test directory");
+    for file in files {
+        fs::write(dir.join(file), []).expect("Create test file");
+    }
+    return dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{auto_resize, auto_resize_srgb, linear_to_srgb, load_image_adv, srgb_to_linear, test_dir};
+    use image::{ColorType, DynamicImage, ImageBuffer, Luma, Rgb, Rgb32FImage, RgbImage};
+
+    #[test]
+    fn resize_linear_light() {
+        for v in [0.0, 0.02, 0.2, 0.5, 1.0] {
+            assert!((linear_to_srgb(srgb_to_linear(v)) - v).abs() < 1e-5);
+        }
+
+        // Black and white stripes average to mid-gray in linear light, which is brighter once encoded as sRGB
+        let stripes = RgbImage::from_fn(8, 8, |x, _| Rgb([(x % 2 * 255) as u8; 3]));
+        let (encoded, _, _) = auto_resize(DynamicImage::from(stripes.clone()), 4, 4);
+        let (linear, width, height) = auto_resize_srgb(DynamicImage::from(stripes), 4, 4);
+        assert_eq!((width, height), (4, 4));
+        assert_eq!(linear.color(), image::ColorType::Rgb8);
+        let encoded = encoded.to_rgb8().get_pixel(1, 1)[0];
+        let linear = linear.to_rgb8().get_pixel(1, 1)[0];
+        assert!(encoded.abs_diff(128) < 8, "{0}", encoded);
+        assert!(linear.abs_diff(188) < 8, "{0}", linear);
+    }
+
+    #[test]
+    fn load_source_formats() {
+        let dir = test_dir("source_formats", &[]);
+
+        // Float sources are linear, so sRGB maps are encoded after resizing
+        let exr = dir.join("mat_a_basecolor.exr");
+        Rgb32FImage::from_pixel(8, 8, Rgb([0.214, 0.214, 0.214])).save(&exr).expect("Write EXR source");
+        let (img, width, height) = load_image_adv(&exr, 4, ColorType::Rgb8, true).expect("Load EXR source");
+        assert_eq!((width, height, img.color()), (4, 4, ColorType::Rgb8));
+        assert!(img.to_rgb8().get_pixel(0, 0)[0].abs_diff(128) <= 1);
+        let (img, _, _) = load_image_adv(&exr, 4, ColorType::L8, false).expect("Load EXR source");
+        assert!(img.to_luma8().get_pixel(0, 0)[0].abs_diff(55) <= 1);
+
+        // 16-bit sources keep their precision
+        let tiff = dir.join("mat_a_height.tiff");
+        ImageBuffer::<Luma<u16>, Vec<u16>>::from_pixel(4, 4, Luma([12345])).save(&tiff).expect("Write TIFF source");
+        let (img, _, _) = load_image_adv(&tiff, 4, ColorType::L16, false).expect("Load TIFF source");
+        assert_eq!(img.to_luma16().get_pixel(0, 0)[0], 12345);
+
+        // 16-bit sRGB sources are resized before they're reduced to 8 bits
+        let png = dir.join("mat_a_basecolor.png");
+        let stripes = |x: u32, _| if x.is_multiple_of(2) { Rgb([481u16; 3]) } else { Rgb([12481u16; 3]) };
+        ImageBuffer::from_fn(8, 8, stripes).save(&png).expect("Write PNG source");
+        let (img, width, height) = load_image_adv(&png, 4, ColorType::Rgb8, true).expect("Load PNG source");
+        let (reference, _, _) = load_image_adv(&png, 4, ColorType::Rgba32F, true).expect("Load PNG source");
+        assert_eq!((width, height), (4, 4));
+        assert_eq!(img.to_rgb8().get_pixel(1, 1)[0], reference.to_rgb8().get_pixel(1, 1)[0]);
+    }
 }
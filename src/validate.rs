@@ -0,0 +1,239 @@
+use crate::cli::verbosity;
+use crate::format::output::{self, Container};
+use crate::model::{self, Diagnostics, MaterialConfig};
+use crate::naming::SourceDir;
+use crate::{budget, pipeline, util};
+use std::path::{Path, PathBuf};
+
+/// Returns true if a source map of the material can be found under the input directory,
+/// either as its own texture or within a declared packed source.
+fn source_exists(input_dir: &SourceDir, mat: &MaterialConfig, map: &str) -> bool {
+    if mat.naming.source(input_dir, &mat.name, map).is_some() {
+        return true;
+    }
+    return mat.unpack.iter().any(|(source, layout)| {
+        layout.find(map).is_some() && mat.naming.source(input_dir, &mat.name, source).is_some()
+    });
+}
+
+/// Checks that every map of a material has its source textures available, and that every map name is known.
+/// Problems are recorded in `diag`.
+pub fn check_material(input_dir: &SourceDir, mat: &MaterialConfig, diag: &mut Diagnostics) {
+    for (i, channel) in mat.channels.iter().enumerate() {
+        let path = format!("materials.{0}.channels[{1}]", mat.name, i);
+
+        let Some(layout) = channel.layout.as_ref() else {
+            if !util::map_is_known(&channel.name) {
+                diag.warn(&path, &format!("unknown map '{0}' will be treated as 8-bit RGB", channel.name));
+            }
+            if !source_exists(input_dir, mat, &channel.name) {
+                let expected = mat.naming.source_path(input_dir, &mat.name, &channel.name);
+                diag.error(&path, &format!("source map not found at {0}", expected.display()));
+            }
+            continue;
+        };
+
+        // Packed textures use an existing packed texture if present, otherwise each slot falls back to its default value
+        if source_exists(input_dir, mat, &channel.name) {
+            continue;
+        }
+        let maps: Vec<&String> = layout.slots.iter().flatten().filter_map(|slot| slot.map.as_ref()).collect();
+        let missing: Vec<&String> = maps.iter().copied().filter(|map| !source_exists(input_dir, mat, map)).collect();
+        if !maps.is_empty() && missing.len() == maps.len() {
+            diag.error(&path, &format!("no source maps found for packed texture '{0}'", channel.name));
+        } else if !missing.is_empty() {
+            let names: Vec<&str> = missing.iter().map(|map| map.as_str()).collect();
+            diag.warn(&path, &format!("missing source maps {0:?} will be filled with default values", names));
+        }
+    }
+}
+
+/// Checks that each source map of a material has only one source texture, such as both a `.png` and a `.tga`,
+/// warning about any that have several. Problems are recorded in `diag`.
+/// Sources named after another map of the material belong to that map, so they are not counted as aliases.
+pub fn check_ambiguity(input_dir: &SourceDir, mat: &MaterialConfig, diag: &mut Diagnostics) {
+    let maps = mat.source_maps();
+    for map in maps.iter() {
+        let mut sources = mat.naming.sources(input_dir, &mat.name, map);
+        for other in maps.iter().filter(|other| *other != map) {
+            let owned = mat.naming.own_sources(input_dir, &mat.name, other);
+            sources.retain(|path| !owned.contains(path));
+        }
+        if sources.len() > 1 {
+            let names: Vec<String> = sources.iter().map(|path| path.display().to_string()).collect();
+            diag.warn(
+                &format!("materials.{0}", mat.name),
+                &format!("map '{0}' has several source textures {1:?}, using the first", map, names),
+            );
+        }
+    }
+}
+
+/// Checks that the source textures of a material were authored at its declared `res_base`, warning about any that were not.
+/// Only image headers are read. Problems are recorded in `diag`.
+pub fn check_resolution(input_dir: &SourceDir, mat: &MaterialConfig, diag: &mut Diagnostics) {
+    let Some(res_base) = mat.res_base else {
+        return;
+    };
+    let mut checked: Vec<PathBuf> = Vec::new();
+    for channel in mat.channels.iter() {
+        let layout = channel.layout.as_ref();
+        for source in pipeline::channel_sources(input_dir, &mat.name, &channel.name, layout, &mat.unpack, &mat.naming) {
+            if checked.contains(&source) {
+                continue;
+            }
+            if let Ok((width, height)) = image::image_dimensions(&source) {
+                if width.max(height) != res_base {
+                    diag.warn(
+                        &format!("materials.{0}.res_base", mat.name),
+                        &format!("source {0} is {1}x{2}, expected {3}px", source.display(), width, height, res_base),
+                    );
+                }
+            }
+            checked.push(source);
+        }
+    }
+}
+
+/// Prints the outputs a material would be built into, with their estimated GPU memory, without loading any images.
+fn print_plan(input_dir: &SourceDir, output_dir: &Path, mat: &MaterialConfig) {
+    for channel in mat.channels.iter() {
+        let out_path = mat.naming.output_path(output_dir, &mat.name, &channel.name, mat.format.extension());
+        let mut ct = pipeline::output_color(&channel.name, channel.layout.as_ref(), mat.alpha);
+        if channel.name.eq("normal") {
+            ct = mat.format.normals.color_type();
+        }
+
+        let mut description = match mat.format.container {
+            Container::Png => format!("{0:?}", ct),
+            _ => format!("{0:?}", output::pixel_format(ct, &mat.format)),
+        };
+        if mat.is_srgb(&channel.name) {
+            description.push_str(", sRGB");
+        }
+        if mat.format.mips() {
+            description.push_str(", mips");
+        }
+        println!(
+            "\t{0} ({1}, max {2}px, {3})",
+            out_path.to_str().unwrap(),
+            description,
+            mat.max_dimension,
+            budget::format_bytes(budget::channel_bytes(input_dir, mat, channel, mat.max_dimension))
+        );
+    }
+}
+
+/// Validates an IronPress Pipeline JSON file without processing any images.
+/// Checks the structure of the file, that every source map exists at its declared resolution, and that every map name is known,
+/// then prints the planned outputs.
+/// Returns TRUE if the pipeline is valid, FALSE otherwise.
+pub fn from_file(config_file: &Path) -> bool {
+    let dir = config_file.parent().unwrap(); // Get working directory
+
+    let mut diagnostics = Diagnostics::default();
+    let mut config = model::load(config_file, &mut diagnostics);
+    if let Some(config) = config.as_mut() {
+        let input_dir = SourceDir::read(&dir.join(Path::new(&config.input)));
+        for mat in config.materials.iter() {
+            check_material(&input_dir, mat, &mut diagnostics);
+            check_ambiguity(&input_dir, mat, &mut diagnostics);
+            check_resolution(&input_dir, mat, &mut diagnostics);
+        }
+        budget::enforce(config, &input_dir, &mut diagnostics);
+    }
+    diagnostics.print();
+
+    let Some(config) = config.filter(|_| diagnostics.errors.is_empty()) else {
+        eprintln!(
+            "Pipeline at {0} has {1} error(s) and {2} warning(s)",
+            config_file.to_str().unwrap(),
+            diagnostics.errors.len(),
+            diagnostics.warnings.len()
+        );
+        return false;
+    };
+
+    if verbosity::is_quiet() {
+        return true;
+    }
+    let input_dir = SourceDir::read(&dir.join(Path::new(&config.input)));
+    let output_dir = dir.join(Path::new(&config.output));
+    let num_maps: usize = config.materials.iter().map(|mat| mat.channels.len()).sum();
+    println!("Planned outputs:");
+    for mat in config.materials.iter() {
+        print_plan(&input_dir, &output_dir, mat);
+    }
+    println!(
+        "Pipeline at {0} is valid, with {1} materials and {2} maps ({3} warning(s))",
+        config_file.to_str().unwrap(),
+        config.materials.len(),
+        num_maps,
+        diagnostics.warnings.len()
+    );
+    return true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_ambiguity, check_material, check_resolution};
+    use image::RgbImage;
+    use crate::model::{self, Diagnostics};
+    use crate::naming::SourceDir;
+    use crate::util::test_dir;
+
+    #[test]
+    fn check_sources() {
+        let input_dir = test_dir("validate_sources", &["mat_a_basecolor.png", "mat_a_roughness.png", "mat_b_orm.png", "mat_c_basecolor.tga", "mat_d_basecolor.png", "mat_d_diffuse.png"]);
+        RgbImage::new(16, 8).save(input_dir.join("mat_c_basecolor.png")).expect("Create test source image");
+        RgbImage::new(8, 8).save(input_dir.join("mat_c_normal.png")).expect("Create test source image");
+        let sources = SourceDir::read(&input_dir);
+
+        let mut diag = Diagnostics::default();
+        let config = model::parse(
+            r#"{
+                "input": "./",
+                "output": "./out/",
+                "materials": {
+                    "mat_a": { "max_dimension": 64, "channels": ["basecolor", "normal", "arm", "emissive", "orm"] },
+                    "mat_b": { "max_dimension": 64, "unpack": { "orm": "orm" }, "channels": ["roughness", "arm"] },
+                    "mat_c": { "res_base": 16, "scale": 0.5, "channels": ["basecolor", "normal"] },
+                    "mat_d": { "max_dimension": 64, "channels": ["basecolor", "diffuse"] }
+                }
+            }"#,
+            None,
+            &mut diag,
+        )
+        .expect("Valid config");
+        for mat in config.materials.iter() {
+            check_material(&sources, mat, &mut diag);
+            check_ambiguity(&sources, mat, &mut diag);
+            check_resolution(&sources, mat, &mut diag);
+        }
+        assert_eq!(config.materials[2].max_dimension, 8);
+
+        let normal = input_dir.join("mat_a_normal.png");
+        let emissive = input_dir.join("mat_a_emissive.png");
+        assert_eq!(
+            diag.errors,
+            vec![
+                format!("materials.mat_a.channels[1]: source map not found at {0}", normal.display()),
+                format!("materials.mat_a.channels[3]: source map not found at {0}", emissive.display()),
+            ]
+        );
+        assert_eq!(
+            diag.warnings,
+            vec![
+                "materials.mat_a.channels[2]: missing source maps [\"ao\", \"metallic\"] will be filled with default values",
+                "materials.mat_a.channels[3]: unknown map 'emissive' will be treated as 8-bit RGB",
+                "materials.mat_a.channels[4]: missing source maps [\"ao\", \"metallic\"] will be filled with default values",
+                &format!(
+                    "materials.mat_c: map 'basecolor' has several source textures [{0:?}, {1:?}], using the first",
+                    input_dir.join("mat_c_basecolor.png").display().to_string(),
+                    input_dir.join("mat_c_basecolor.tga").display().to_string()
+                ),
+                &format!("materials.mat_c.res_base: source {0} is 8x8, expected 16px", input_dir.join("mat_c_normal.png").display()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::cli::args::BuildOptions;
+use crate::cli::verbosity;
+use crate::model::{self, Diagnostics, PipelineConfig};
+use crate::pipeline;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Modification time and size of each watched file.
+type Snapshot = HashMap<PathBuf, (SystemTime, u64)>;
+
+/// Records the modification time and size of a file into the snapshot, if it exists.
+fn record(path: &Path, snapshot: &mut Snapshot) {
+    if let Ok(metadata) = fs::metadata(path) {
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        snapshot.insert(path.to_path_buf(), (modified, metadata.len()));
+    }
+}
+
+/// Takes a snapshot of the pipeline file, and every file in the input directory.
+/// Subdirectories are not watched, as source textures are only read from the input directory itself.
+fn snapshot(config_file: &Path, input_dir: Option<&Path>) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    record(config_file, &mut snapshot);
+    if let Some(entries) = input_dir.and_then(|dir| fs::read_dir(dir).ok()) {
+        for entry in entries.flatten() {
+            record(&entry.path(), &mut snapshot);
+        }
+    }
+    return snapshot;
+}
+
+/// Returns every file that was added, removed, or modified between two snapshots.
+fn changed_files(old: &Snapshot, new: &Snapshot) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = new
+        .iter()
+        .filter(|(path, state)| old.get(*path) != Some(*state))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(old.keys().filter(|path| !new.contains_key(*path)).cloned());
+    return changed;
+}
+
+/// Returns the names of the materials that source textures belong to, judging by the file names their sources may have.
+fn affected_materials(config: &PipelineConfig, files: &[PathBuf]) -> Vec<String> {
+    return config
+        .materials
+        .iter()
+        .filter(|mat| {
+            let maps = mat.source_maps();
+            files.iter().any(|file| maps.iter().any(|map| mat.naming.is_source(file, &mat.name, map)))
+        })
+        .map(|mat| mat.name.clone())
+        .collect();
+}
+
+/// Loads a pipeline file without printing its diagnostics, which were already reported when it was built.
+fn load_quietly(config_file: &Path) -> Option<PipelineConfig> {
+    return model::load(config_file, &mut Diagnostics::default());
+}
+
+/// Returns the input directory of a pipeline, if the pipeline file is valid.
+fn input_dir(config_file: &Path) -> Option<PathBuf> {
+    let config = load_quietly(config_file)?;
+    return Some(config_file.parent().unwrap().join(Path::new(&config.input)));
+}
+
+/// Builds an IronPress Pipeline JSON file, then watches it and its input directory for changes, rebuilding textures as they change.
+/// Changes are collected until no file has changed for `debounce`, so a burst of writes from an export tool only triggers one rebuild.
+/// Changes to source textures only rebuild the materials they belong to, while changes to the pipeline file rebuild every material.
+/// Builds are incremental, so only maps whose sources or settings changed are converted.
+/// Pipeline diagnostics are only printed when the pipeline file itself is built.
+/// Returns false if the initial build fails, otherwise runs until interrupted.
+pub fn from_file(config_file: &Path, options: &BuildOptions, debounce: Duration) -> bool {
+    if !pipeline::from_file(config_file, options) {
+        eprintln!("Initial build of {0} failed, not watching for changes", config_file.to_str().unwrap());
+        return false;
+    }
+    let mut input = input_dir(config_file);
+    let mut last = snapshot(config_file, input.as_deref());
+    if !verbosity::is_quiet() {
+        println!("Watching {0} for changes, press Ctrl+C to stop", config_file.to_str().unwrap());
+    }
+
+    let mut pending: Vec<PathBuf> = Vec::new();
+    let mut last_change = Instant::now();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let current = snapshot(config_file, input.as_deref());
+        let changed = changed_files(&last, &current);
+        last = current;
+        if !changed.is_empty() {
+            pending.extend(changed);
+            last_change = Instant::now();
+            continue;
+        }
+
+        // Wait until changes settle down before rebuilding
+        if pending.is_empty() || last_change.elapsed() < debounce {
+            continue;
+        }
+        let changed: Vec<PathBuf> = std::mem::take(&mut pending);
+        if verbosity::is_verbose() {
+            for file in changed.iter() {
+                println!("Changed {0}", file.to_str().unwrap());
+            }
+        }
+
+        // Pipeline changes can affect any material, and may move the input directory
+        if changed.iter().any(|file| file == config_file) {
+            pipeline::from_file(config_file, options);
+            input = input_dir(config_file);
+            last = snapshot(config_file, input.as_deref());
+            continue;
+        }
+
+        let Some(config) = load_quietly(config_file) else {
+            continue;
+        };
+        let mut materials = affected_materials(&config, &changed);
+        if !options.materials.is_empty() {
+            materials.retain(|name| options.materials.contains(name));
+        }
+        if materials.is_empty() {
+            continue;
+        }
+
+        let mut affected = options.clone();
+        affected.materials = materials;
+        pipeline::from_config(config_file, config, &affected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{affected_materials, changed_files, Snapshot};
+    use crate::model::{self, Diagnostics};
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+
+    #[test]
+    fn detect_affected() {
+        let mut old = Snapshot::new();
+        old.insert(PathBuf::from("in/mat_a_basecolor.png"), (SystemTime::UNIX_EPOCH, 10));
+        old.insert(PathBuf::from("in/mat_ab_normal.png"), (SystemTime::UNIX_EPOCH, 10));
+        old.insert(PathBuf::from("in/mat_b_arm.png"), (SystemTime::UNIX_EPOCH, 10));
+        let mut new = old.clone();
+        new.insert(PathBuf::from("in/mat_a_basecolor.png"), (SystemTime::UNIX_EPOCH, 20));
+        new.remove(&PathBuf::from("in/mat_b_arm.png"));
+        new.insert(PathBuf::from("in/notes.txt"), (SystemTime::UNIX_EPOCH, 1));
+
+        let mut changed = changed_files(&old, &new);
+        changed.sort();
+        assert_eq!(
+            changed,
+            vec![
+                PathBuf::from("in/mat_a_basecolor.png"),
+                PathBuf::from("in/mat_b_arm.png"),
+                PathBuf::from("in/notes.txt"),
+            ]
+        );
+
+        let mut diag = Diagnostics::default();
+        let config = model::parse(
+            r#"{
+                "input": "./in/",
+                "output": "./out/",
+                "materials": {
+                    "mat_a": { "max_dimension": 64, "channels": ["basecolor"] },
+                    "mat_ab": { "max_dimension": 64, "channels": ["normal"] },
+                    "mat_b": { "max_dimension": 64, "channels": ["arm"] },
+                    "mat_c": { "max_dimension": 64, "channels": ["arm"] }
+                }
+            }"#,
+            None,
+            &mut diag,
+        )
+        .expect("Valid config");
+        assert_eq!(affected_materials(&config, &changed), vec!["mat_a", "mat_b"]);
+    }
+}